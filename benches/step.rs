@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flags::physics::{FlagParams, Physics, WorldBounds};
+use glam::vec2;
+
+// steps per iteration, at the dt of the demo with 10 sub-steps
+const STEPS: usize = 100;
+const DT: f32 = 1.0 / 600.0;
+
+fn flag(width: usize, height: usize) -> FlagParams {
+    FlagParams::builder()
+        .corner(vec2(100.0, 100.0))
+        .size(1000.0)
+        .grid(width, height)
+        .shear(true)
+        .gravity(vec2(200.0, 50.0))
+        .build()
+        .unwrap()
+}
+
+// reported as steps per second for each grid size
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(STEPS as u64));
+    for (width, height) in [(50, 30), (100, 100), (200, 200)] {
+        let id = BenchmarkId::from_parameter(format!("{}x{}", width, height));
+        group.bench_function(id, |b| {
+            let mut physics = Physics::new(&[flag(width, height)]);
+            physics.set_bounds(WorldBounds::from_size(vec2(1500.0, 1500.0)));
+            b.iter(|| {
+                for _ in 0..STEPS {
+                    physics.step(DT);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// the demo flag tiled in a square grid, to load the parallel link solve with many flags
+fn many_flags(c: &mut Criterion) {
+    let mut group = c.benchmark_group("many_flags");
+    group.throughput(Throughput::Elements(STEPS as u64));
+    for side in [2, 4, 8] {
+        let id = BenchmarkId::from_parameter(format!("{}x{}", side, side));
+        group.bench_function(id, |b| {
+            let spacing = vec2(1100.0, 700.0);
+            let mut physics = Physics::grid_of_flags(side, side, spacing, &flag(50, 30));
+            b.iter(|| {
+                for _ in 0..STEPS {
+                    physics.step(DT);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, step, many_flags);
+criterion_main!(benches);
@@ -0,0 +1,203 @@
+use std::{fs::File, io, thread};
+
+use image::RgbaImage;
+
+use miniquad::*;
+
+// Records the frames drawn into an offscreen render target, which is smaller than the window to
+// keep the GIF small. Frames are kept raw in memory and only encoded when the capture stops.
+pub struct GifCapture {
+    texture: Texture,
+    pass: RenderPass,
+    width: u16,
+    height: u16,
+    // frames drawn between two recorded ones, plus one
+    frame_interval: usize,
+    max_frames: usize,
+    // frames still to draw before the next recorded one
+    skip: usize,
+    frames: Vec<Vec<u8>>,
+}
+
+impl GifCapture {
+    pub fn new(
+        ctx: &mut Context,
+        width: u16,
+        height: u16,
+        frame_interval: usize,
+        max_frames: usize,
+    ) -> GifCapture {
+        let texture = Texture::new_render_texture(
+            ctx,
+            TextureParams {
+                width: width as u32,
+                height: height as u32,
+                format: TextureFormat::RGBA8,
+                ..Default::default()
+            },
+        );
+        let pass = RenderPass::new(ctx, texture, None);
+        GifCapture {
+            texture,
+            pass,
+            width,
+            height,
+            frame_interval: frame_interval.max(1),
+            max_frames,
+            skip: 0,
+            frames: vec![],
+        }
+    }
+
+    // whether the current frame should also be drawn into `pass` and then `record`ed
+    pub fn wants_frame(&self) -> bool {
+        self.skip == 0
+    }
+
+    pub fn pass(&self) -> RenderPass {
+        self.pass
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frames.len() >= self.max_frames
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    // called once per drawn frame, reads the render target back when the frame is recorded
+    pub fn record(&mut self) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        self.skip = self.frame_interval - 1;
+        if !self.is_full() {
+            let rows = read_rows(self.texture, self.width as u32, self.height as u32);
+            self.frames.push(rows);
+        }
+    }
+
+    // encodes the recorded frames on a background thread, `frame_time` being the duration of one
+    // drawn frame in seconds
+    pub fn finish(self, ctx: &mut Context, path: String, frame_time: f32) {
+        self.pass.delete(ctx);
+        self.texture.delete();
+
+        let (width, height) = (self.width, self.height);
+        // GIF delays are in hundredths of a second
+        let delay = (frame_time * self.frame_interval as f32 * 100.0).round() as u16;
+        let frames = self.frames;
+        thread::spawn(move || match encode(&path, width, height, delay, frames) {
+            Ok(()) => println!("animation written to {}", path),
+            Err(e) => eprintln!("could not write {}: {}", path, e),
+        });
+    }
+}
+
+// Writes the frames drawn into a window sized render target as numbered PNG files, encoding each
+// one on a background thread as soon as it is read back.
+pub struct PngCapture {
+    texture: Texture,
+    pass: RenderPass,
+    width: u32,
+    height: u32,
+    // files are named `<prefix>.png` for a single frame and `<prefix>-0000.png` onwards otherwise
+    prefix: String,
+    num_frames: usize,
+    written: usize,
+}
+
+impl PngCapture {
+    pub fn new(
+        ctx: &mut Context,
+        width: u32,
+        height: u32,
+        prefix: String,
+        num_frames: usize,
+    ) -> PngCapture {
+        let texture = Texture::new_render_texture(
+            ctx,
+            TextureParams {
+                width,
+                height,
+                format: TextureFormat::RGBA8,
+                ..Default::default()
+            },
+        );
+        let pass = RenderPass::new(ctx, texture, None);
+        PngCapture {
+            texture,
+            pass,
+            width,
+            height,
+            prefix,
+            num_frames,
+            written: 0,
+        }
+    }
+
+    pub fn pass(&self) -> RenderPass {
+        self.pass
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.written >= self.num_frames
+    }
+
+    pub fn num_written(&self) -> usize {
+        self.written
+    }
+
+    // called after every frame drawn into `pass`
+    pub fn record(&mut self) {
+        if self.is_done() {
+            return;
+        }
+        let path = if self.num_frames == 1 {
+            format!("{}.png", self.prefix)
+        } else {
+            format!("{}-{:04}.png", self.prefix, self.written)
+        };
+        self.written += 1;
+        let rows = read_rows(self.texture, self.width, self.height);
+        let (width, height) = (self.width, self.height);
+        thread::spawn(move || {
+            let image = RgbaImage::from_raw(width, height, rows).expect("one pixel per texel");
+            if let Err(e) = image.save(&path) {
+                eprintln!("could not write {}: {}", path, e);
+            }
+        });
+    }
+
+    pub fn finish(self, ctx: &mut Context) {
+        self.pass.delete(ctx);
+        self.texture.delete();
+    }
+}
+
+// reads a render target back as RGBA rows, top row first
+fn read_rows(texture: Texture, width: u32, height: u32) -> Vec<u8> {
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    texture.read_pixels(&mut pixels);
+    // the render target is stored bottom row first
+    pixels
+        .chunks_exact(width as usize * 4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+fn encode(path: &str, width: u16, height: u16, delay: u16, frames: Vec<Vec<u8>>) -> io::Result<()> {
+    let to_io = |e: gif::EncodingError| io::Error::other(e);
+    let mut encoder = gif::Encoder::new(File::create(path)?, width, height, &[]).map_err(to_io)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(to_io)?;
+    for mut pixels in frames {
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(to_io)?;
+    }
+    Ok(())
+}
@@ -1,22 +1,99 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
+// Mutable sub-slices of a slice for a set of disjoint index ranges, each yielded with the index
+// of its first element, so they can be handed to different threads.
+//
+// SAFETY: the chunks are carved out of `v` through raw pointers, which is sound because
+// - `v` comes from a `&'a mut [T]`, so nothing else can reach its elements for `'a`,
+// - every range lies within `v` and no two of them share an element, as checked by
+//   `from_ranges`, or as the caller of `new` guarantees by passing sorted breakpoints within
+//   the slice, so the yielded slices never alias each other,
+// - each range is yielded at most once, `curr_ind` only moving forward.
 pub struct ChunksMutIndices<'a, T: 'a> {
     v: *mut [T],
-    breakpoints: &'a [usize],
+    ranges: Ranges<'a>,
     curr_ind: usize,
     _marker: PhantomData<&'a mut T>,
 }
 
+// the breakpoints are turned into ranges as the chunks are yielded, so `new` doesn't allocate
+enum Ranges<'a> {
+    Breakpoints(&'a [usize]),
+    Given(Vec<Range<usize>>),
+}
+
+impl Ranges<'_> {
+    fn get(&self, i: usize, len: usize) -> Option<Range<usize>> {
+        match self {
+            Ranges::Breakpoints(breakpoints) => {
+                let start = *breakpoints.get(i)?;
+                Some(start..breakpoints.get(i + 1).copied().unwrap_or(len))
+            }
+            Ranges::Given(ranges) => ranges.get(i).cloned(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Ranges::Breakpoints(breakpoints) => breakpoints.len(),
+            Ranges::Given(ranges) => ranges.len(),
+        }
+    }
+}
+
 impl<'a, T: 'a + Sync> ChunksMutIndices<'a, T> {
+    // chunks from each breakpoint to the next one, the last running to the end of the slice
     #[inline]
     pub fn new(slice: &'a mut [T], breakpoints: &'a [usize]) -> Self {
+        debug_assert!(
+            breakpoints.windows(2).all(|pair| pair[0] <= pair[1])
+                && breakpoints.last().is_none_or(|&last| last <= slice.len()),
+            "breakpoints {:?} are not sorted within {} elements",
+            breakpoints,
+            slice.len()
+        );
         Self {
             v: slice,
-            breakpoints,
+            ranges: Ranges::Breakpoints(breakpoints),
             curr_ind: 0,
             _marker: PhantomData,
         }
     }
+
+    // Chunks for arbitrary `ranges`, yielded in the given order, which needs not be sorted.
+    // Fails if a range is reversed, reaches past the slice or overlaps another one. Empty
+    // ranges are allowed anywhere and yield empty chunks. The solver only splits on breakpoints.
+    #[allow(dead_code)]
+    pub fn from_ranges(
+        slice: &'a mut [T],
+        ranges: impl IntoIterator<Item = Range<usize>>,
+    ) -> Result<Self, String> {
+        let ranges = ranges.into_iter().collect::<Vec<Range<usize>>>();
+        if let Some(r) = ranges
+            .iter()
+            .find(|r| r.start > r.end || r.end > slice.len())
+        {
+            return Err(format!(
+                "range {:?} is not within a slice of {} elements",
+                r,
+                slice.len()
+            ));
+        }
+        let mut sorted = ranges
+            .iter()
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<&Range<usize>>>();
+        sorted.sort_by_key(|r| r.start);
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0].end > pair[1].start) {
+            return Err(format!("ranges {:?} and {:?} overlap", pair[0], pair[1]));
+        }
+        Ok(Self {
+            v: slice,
+            ranges: Ranges::Given(ranges),
+            curr_ind: 0,
+            _marker: PhantomData,
+        })
+    }
 }
 
 impl<'a, T> Iterator for ChunksMutIndices<'a, T> {
@@ -24,22 +101,102 @@ impl<'a, T> Iterator for ChunksMutIndices<'a, T> {
 
     #[inline]
     fn next(&mut self) -> Option<(&'a mut [T], usize)> {
-        if self.v.is_empty() || self.curr_ind >= self.breakpoints.len() {
-            None
-        } else {
-            let siz = if self.curr_ind < self.breakpoints.len() - 1 {
-                self.breakpoints[self.curr_ind + 1] - self.breakpoints[self.curr_ind]
-            } else {
-                self.v.len()
-            };
-            self.curr_ind += 1;
-            // SAFETY: The self.v contract ensures that any split_at_mut is valid.
-            let (head, tail) = unsafe { self.v.split_at_mut(siz) };
-            self.v = tail;
-            // SAFETY: Nothing else points to or will point to the contents of this slice.
-            Some(unsafe { (&mut *head, self.breakpoints[self.curr_ind - 1]) })
-        }
+        let range = self.ranges.get(self.curr_ind, self.v.len())?;
+        self.curr_ind += 1;
+        debug_assert!(range.start <= range.end && range.end <= self.v.len());
+        // SAFETY: The range is within `v`, see `from_ranges`.
+        let head = unsafe { (self.v as *mut T).add(range.start) };
+        // SAFETY: Nothing else points to or will point to the contents of this slice.
+        Some(unsafe {
+            (
+                std::slice::from_raw_parts_mut(head, range.len()),
+                range.start,
+            )
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.ranges.len() - self.curr_ind;
+        (left, Some(left))
     }
 }
 
 unsafe impl<'a, T> Send for ChunksMutIndices<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the chunks of `ranges` of 0..10, each with its start
+    fn chunks(ranges: Vec<Range<usize>>) -> Result<Vec<(Vec<u32>, usize)>, String> {
+        let mut values = (0..10).collect::<Vec<u32>>();
+        let chunks = ChunksMutIndices::from_ranges(&mut values, ranges)?;
+        Ok(chunks.map(|(c, start)| (c.to_vec(), start)).collect())
+    }
+
+    #[test]
+    fn breakpoints_split_the_whole_slice() {
+        let mut values = (0..10).collect::<Vec<u32>>();
+        let sizes = ChunksMutIndices::new(&mut values, &[0, 3, 3, 7])
+            .map(|(c, start)| (c.len(), start))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [(3, 0), (0, 3), (4, 3), (3, 7)]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not sorted")]
+    fn unsorted_breakpoints_are_caught_in_debug_builds() {
+        let mut values = (0..10).collect::<Vec<u32>>();
+        ChunksMutIndices::new(&mut values, &[0, 6, 3]).for_each(drop);
+    }
+
+    #[test]
+    fn out_of_order_ranges_are_yielded_as_given() {
+        let chunks = chunks(vec![6..9, 0..2, 2..4]).unwrap();
+        assert_eq!(
+            chunks,
+            [(vec![6, 7, 8], 6), (vec![0, 1], 0), (vec![2, 3], 2)]
+        );
+    }
+
+    #[test]
+    fn overlapping_ranges_are_rejected() {
+        assert!(chunks(vec![0..4, 3..6]).is_err());
+        assert!(chunks(vec![5..8, 0..6]).is_err());
+        assert!(chunks(vec![2..5, 2..5]).is_err());
+        // touching is not overlapping
+        assert!(chunks(vec![3..6, 0..3]).is_ok());
+    }
+
+    #[test]
+    fn reversed_or_out_of_bounds_ranges_are_rejected() {
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = vec![0..1, 4..2];
+        assert!(chunks(reversed).is_err());
+        assert!(chunks(vec![0..1, 8..11]).is_err());
+    }
+
+    #[test]
+    fn empty_ranges_may_sit_anywhere() {
+        let chunks = chunks(vec![3..3, 0..5, 4..4]).unwrap();
+        assert_eq!(chunks[0], (vec![], 3));
+        assert_eq!(chunks[2], (vec![], 4));
+    }
+
+    #[test]
+    fn chunks_can_be_written_in_parallel() {
+        use rayon::prelude::*;
+
+        let mut values = vec![0; 10];
+        ChunksMutIndices::from_ranges(&mut values, vec![7..10, 0..3, 4..6])
+            .unwrap()
+            .par_bridge()
+            .for_each(|(chunk, start)| {
+                for (i, v) in chunk.iter_mut().enumerate() {
+                    *v = start + i;
+                }
+            });
+        assert_eq!(values, [0, 1, 2, 0, 4, 5, 0, 7, 8, 9]);
+    }
+}
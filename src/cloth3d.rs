@@ -0,0 +1,292 @@
+use glam::{vec3, Vec3};
+
+// A minimal 3D counterpart of `physics`, kept apart so the 2D solver stays as it is. A single flag
+// hangs from the two ends of its left edge, with structural and shear links, gravity, wind
+// pushing on each triangle along its normal so the cloth billows out of its plane, and spheres
+// it slides around. Y points up.
+
+// fraction of their velocity the nodes lose every step
+const DAMPING: f32 = 0.01;
+// fraction of a link's error each end corrects per step, see `physics::FlagParams::stiffness`
+const STIFFNESS: f32 = 0.5;
+// force of the wind on a triangle per unit of area and of wind speed along its normal
+const WIND_DRAG: f32 = 0.002;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    pos: Vec3,
+    last_pos: Vec3,
+    pinned: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    node1: usize,
+    node2: usize,
+    resting_distance: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+pub struct Cloth {
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+    triangles: Vec<[usize; 3]>,
+    gravity: Vec3,
+    wind: Vec3,
+    spheres: Vec<Sphere>,
+    // scratch of `wind_forces`, the force on each node
+    forces: Vec<Vec3>,
+}
+
+impl Cloth {
+    // a `width` x `height` flag `size` wide, in the plane facing +Z, its top left node at `corner`
+    pub fn new(corner: Vec3, size: f32, width: usize, height: usize) -> Result<Cloth, String> {
+        if width < 2 || height < 2 {
+            return Err(format!(
+                "a flag needs at least 2x2 nodes, got {}x{}",
+                width, height
+            ));
+        }
+        if size <= 0.0 || !size.is_finite() {
+            return Err(format!("the size must be positive, got {}", size));
+        }
+        let spacing = size / width as f32;
+        let nodes = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let pos = corner + vec3(x as f32, -(y as f32), 0.0) * spacing;
+                Node {
+                    pos,
+                    last_pos: pos,
+                    pinned: x == 0 && (y == 0 || y == height - 1),
+                }
+            })
+            .collect::<Vec<Node>>();
+
+        let cells = (0..height - 1)
+            .flat_map(|y| (0..width - 1).map(move |x| x + y * width))
+            .collect::<Vec<usize>>();
+        let horizontal = (0..height)
+            .flat_map(|y| (0..width - 1).map(move |x| (x + y * width, x + 1 + y * width)));
+        let vertical = (0..height - 1)
+            .flat_map(|y| (0..width).map(move |x| (x + y * width, x + (y + 1) * width)));
+        let shear = cells
+            .iter()
+            .flat_map(|&i| [(i, i + width + 1), (i + 1, i + width)]);
+        let links = horizontal
+            .chain(vertical)
+            .chain(shear)
+            .map(|(node1, node2)| Link {
+                node1,
+                node2,
+                resting_distance: nodes[node1].pos.distance(nodes[node2].pos),
+            })
+            .collect();
+        let triangles = cells
+            .iter()
+            .flat_map(|&i| [[i, i + width, i + 1], [i + 1, i + width, i + width + 1]])
+            .collect();
+
+        Ok(Cloth {
+            nodes,
+            links,
+            triangles,
+            gravity: Vec3::ZERO,
+            wind: Vec3::ZERO,
+            spheres: vec![],
+            forces: vec![],
+        })
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) {
+        self.gravity = gravity;
+    }
+
+    pub fn set_wind(&mut self, wind: Vec3) {
+        self.wind = wind;
+    }
+
+    pub fn wind(&self) -> Vec3 {
+        self.wind
+    }
+
+    pub fn add_sphere(&mut self, sphere: Sphere) {
+        self.spheres.push(sphere);
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        self.wind_forces(dt);
+        for (n, &force) in self.nodes.iter_mut().zip(&self.forces) {
+            if n.pinned {
+                continue;
+            }
+            let diff = (n.pos - n.last_pos) * (1.0 - DAMPING);
+            n.last_pos = n.pos;
+            n.pos += diff + (self.gravity + force) * (dt * dt);
+        }
+
+        for l in &self.links {
+            let (n1, n2) = (self.nodes[l.node1], self.nodes[l.node2]);
+            let diff = n1.pos - n2.pos;
+            let dist = diff.length();
+            if dist == 0.0 {
+                continue;
+            }
+            let correction = diff * ((l.resting_distance - dist) / dist * STIFFNESS);
+            // a pinned end stays put and the other one takes the whole correction
+            match (n1.pinned, n2.pinned) {
+                (false, false) => {
+                    self.nodes[l.node1].pos += correction;
+                    self.nodes[l.node2].pos -= correction;
+                }
+                (false, true) => self.nodes[l.node1].pos += correction * 2.0,
+                (true, false) => self.nodes[l.node2].pos -= correction * 2.0,
+                (true, true) => (),
+            }
+        }
+
+        for n in self.nodes.iter_mut().filter(|n| !n.pinned) {
+            for s in &self.spheres {
+                let v = n.pos - s.center;
+                let dist2 = v.length_squared();
+                if dist2 < s.radius * s.radius && dist2 > 0.0 {
+                    n.pos = s.center + v * (s.radius / dist2.sqrt());
+                }
+            }
+        }
+    }
+
+    // Fills `forces` with the wind on each node. The wind on each triangle is the normal
+    // component of its speed relative to the air, times its area, shared by its three nodes. A
+    // triangle facing the wind catches all of it and one edge on lets it slide by, which is what
+    // makes the cloth billow.
+    fn wind_forces(&mut self, dt: f32) {
+        self.forces.clear();
+        self.forces.resize(self.nodes.len(), Vec3::ZERO);
+        for tri in &self.triangles {
+            let [a, b, c] = tri.map(|i| self.nodes[i]);
+            // twice the area along the normal
+            let normal = (b.pos - a.pos).cross(c.pos - a.pos);
+            let velocity =
+                (a.pos - a.last_pos + b.pos - b.last_pos + c.pos - c.last_pos) / (3.0 * dt);
+            let unit = normal.normalize_or_zero();
+            let force =
+                unit * ((self.wind - velocity).dot(unit) * normal.length() * 0.5 * WIND_DRAG);
+            for &i in tri {
+                self.forces[i] += force / 3.0;
+            }
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn write_points(&self, out: &mut Vec<Vec3>) {
+        out.clear();
+        out.extend(self.nodes.iter().map(|n| n.pos));
+    }
+
+    // pairs of nodes, one per link
+    pub fn get_indices(&self) -> Vec<u32> {
+        self.links
+            .iter()
+            .flat_map(|l| [l.node1 as u32, l.node2 as u32])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 100 wide flag hanging from its left edge at the origin, pulled down by gravity
+    fn cloth(width: usize, height: usize) -> Cloth {
+        let mut cloth = Cloth::new(Vec3::ZERO, 100.0, width, height).unwrap();
+        cloth.set_gravity(vec3(0.0, -200.0, 0.0));
+        cloth
+    }
+
+    fn points(cloth: &Cloth) -> Vec<Vec3> {
+        let mut points = vec![];
+        cloth.write_points(&mut points);
+        points
+    }
+
+    #[test]
+    fn degenerate_cloths_are_rejected() {
+        assert!(Cloth::new(Vec3::ZERO, 100.0, 1, 5).is_err());
+        assert!(Cloth::new(Vec3::ZERO, 100.0, 5, 0).is_err());
+        for size in [0.0, -10.0, f32::NAN] {
+            assert!(Cloth::new(Vec3::ZERO, size, 5, 5).is_err(), "{}", size);
+        }
+        assert!(Cloth::new(Vec3::ZERO, 100.0, 2, 2).is_ok());
+    }
+
+    #[test]
+    fn pinned_corners_stay_put() {
+        let mut cloth = cloth(6, 5);
+        cloth.set_wind(vec3(50.0, 0.0, 300.0));
+        let start = points(&cloth);
+        for _ in 0..200 {
+            cloth.step(1.0 / 60.0);
+        }
+        let end = points(&cloth);
+        // the top and bottom ends of the left edge
+        for i in [0, 4 * 6] {
+            assert_eq!(end[i], start[i], "node {}", i);
+        }
+        assert_ne!(end[5], start[5]);
+    }
+
+    #[test]
+    fn wind_pushes_the_cloth_out_of_its_plane() {
+        let [still, windy] = [Vec3::ZERO, vec3(0.0, 0.0, 300.0)].map(|wind| {
+            let mut cloth = cloth(6, 5);
+            cloth.set_wind(wind);
+            for _ in 0..60 {
+                cloth.step(1.0 / 60.0);
+            }
+            points(&cloth)
+        });
+        assert!(still.iter().all(|p| p.z == 0.0));
+        let free = windy.iter().enumerate().filter(|&(i, _)| i % 6 != 0);
+        assert!(free.clone().all(|(_, p)| p.z > 0.0));
+        // the far edge billows the most
+        assert!(windy[5].z > windy[1].z);
+    }
+
+    #[test]
+    fn no_node_ends_up_inside_the_sphere() {
+        let sphere = Sphere {
+            center: vec3(50.0, -60.0, 10.0),
+            radius: 30.0,
+        };
+        // the sphere starts out through the middle of the cloth
+        let mut cloth = cloth(10, 10);
+        assert!(points(&cloth)
+            .iter()
+            .any(|p| p.distance(sphere.center) < sphere.radius));
+        cloth.set_wind(vec3(0.0, 0.0, 200.0));
+        cloth.add_sphere(sphere);
+        let mut touching = 0;
+        for _ in 0..300 {
+            cloth.step(1.0 / 60.0);
+            let distances = points(&cloth)
+                .iter()
+                .map(|p| p.distance(sphere.center))
+                .collect::<Vec<f32>>();
+            assert!(distances.iter().all(|&d| d >= sphere.radius - 1e-3));
+            if distances.iter().any(|&d| d < sphere.radius + 0.5) {
+                touching += 1;
+            }
+        }
+        // the wind keeps the cloth against the sphere for a while before it slides off
+        assert!(touching > 10, "{}", touching);
+    }
+}
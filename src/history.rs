@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+use crate::physics::{Node, Physics};
+
+// The states of the nodes after the last `capacity` steps, oldest first, to scrub through the
+// recent motion while the simulation is paused.
+pub struct History {
+    capacity: usize,
+    frames: VecDeque<Vec<Node>>,
+    // frame restored by the last scrub, None while following the simulation
+    cursor: Option<usize>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+            cursor: None,
+        }
+    }
+
+    // records the current state, the frames after a scrubbed one are dropped as the simulation
+    // resumes from there
+    pub fn push(&mut self, physics: &Physics) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(cursor) = self.cursor.take() {
+            self.frames.truncate(cursor + 1);
+        }
+        // nodes were added or removed, the older frames can't be restored anymore
+        if self
+            .frames
+            .back()
+            .is_some_and(|f| f.len() != physics.num_nodes())
+        {
+            self.frames.clear();
+        }
+        // reuses the allocation of the oldest frame once full
+        let mut frame = if self.frames.len() < self.capacity {
+            Vec::with_capacity(physics.num_nodes())
+        } else {
+            self.frames.pop_front().unwrap()
+        };
+        frame.clear();
+        frame.extend_from_slice(physics.nodes());
+        self.frames.push_back(frame);
+    }
+
+    // restores the frame `offset` frames before (negative) or after the current one, stopping at
+    // both ends of the history
+    pub fn scrub(&mut self, physics: &mut Physics, offset: isize) {
+        let Some(last) = self.frames.len().checked_sub(1) else {
+            return;
+        };
+        let current = self.cursor.unwrap_or(last);
+        let target = current.saturating_add_signed(offset).min(last);
+        if self.frames[target].len() != physics.num_nodes() {
+            return;
+        }
+        physics.restore_nodes(&self.frames[target]);
+        self.cursor = Some(target);
+    }
+
+    // the frame shown and the number of frames, for display
+    pub fn position(&self) -> (usize, usize) {
+        let len = self.frames.len();
+        (self.cursor.map_or(len, |c| c + 1), len)
+    }
+}
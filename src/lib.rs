@@ -0,0 +1,6 @@
+mod chunk_iter;
+pub mod cloth3d;
+pub mod history;
+pub mod physics;
+mod spatial_hash;
+pub mod trace;
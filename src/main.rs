@@ -1,20 +1,244 @@
-#![feature(slice_ptr_len)]
-#![feature(raw_slice_split)]
+use std::{
+    fs,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use std::time::Instant;
-
-mod chunk_iter;
-mod physics;
+mod capture;
+mod scene;
 mod shader;
+mod text;
+mod view3d;
 
 use miniquad::*;
 
-use glam::{vec2, Mat4, Vec2};
-use physics::{FlagParams, Physics};
+use capture::{GifCapture, PngCapture};
+use clap::Parser;
+use flags::history::History;
+use flags::physics::{
+    ClampMode, ConstraintKind, Edge, Falloff, FlagParams, Gust, LinkHighlight, LinkSolver,
+    Obstacle, ObstacleId, Params, Physics, WindField, WindId, WorldBounds,
+};
+use glam::{vec2, vec4, Mat4, Vec2, Vec4};
+use scene::SceneConfig;
+use serde::{Deserialize, Serialize};
+use text::TextRenderer;
 
-const SUB_STEPS: usize = 10;
+const DT: f32 = 1. / 60.;
+// frame time fed to the fixed-timestep accumulator is clamped so a stall or a pause doesn't
+// trigger a long burst of catch-up steps
+const MAX_FRAME_TIME: f32 = 0.1;
+// limits of the time scale, which a key halves or doubles
+const MIN_TIME_SCALE: f32 = 0.125;
+const MAX_TIME_SCALE: f32 = 4.0;
+// range of the sub-step count when it follows the speed of the cloth
+const ADAPTIVE_SUB_STEPS: (usize, usize) = (2, 40);
 const WIDTH: usize = 1500;
 const HEIGHT: usize = 1500;
+const GRAVITY: Vec2 = vec2(200.0, 50.0);
+// change of the gravity for every press of shift and an arrow key
+const GRAVITY_STEP: f32 = 25.0;
+const STIFFNESS: f32 = 0.5;
+const MAX_PUSH: f32 = 0.001;
+// velocity lost per substep, the cloth keeps 30% of it after a second of 600 substeps
+const DAMPING: f32 = 0.002;
+// change of the demo flag stiffness per key press
+const STIFFNESS_STEP: f32 = 0.05;
+const CLOTH_COLOR: Vec4 = vec4(0.5, 0.8, 1.0, 1.0);
+// color of the back of the cloth in the filled view, where it folds over itself
+const CLOTH_BACK_COLOR: Vec4 = vec4(0.2, 0.3, 0.6, 1.0);
+// strain at which a link is drawn fully red in the strain view
+const MAX_DISPLAY_STRAIN: f32 = 0.2;
+// distance moved in a step at which a node is drawn fully red in the speed view
+const MAX_DISPLAY_SPEED: f32 = 0.5;
+// how far each triangle is pulled towards its centroid in the exploded view
+const EXPLODE_SHRINK: f32 = 0.2;
+// the obstacle placed by the demo, drawn as a disc of `OBSTACLE_SEGMENTS` triangles
+const OBSTACLE_RADIUS: f32 = 120.0;
+const OBSTACLE_SEGMENTS: usize = 48;
+const OBSTACLE_COLOR: Vec4 = vec4(0.8, 0.5, 0.3, 1.0);
+const POLE_COLOR: Vec4 = vec4(0.6, 0.6, 0.65, 1.0);
+const POLE_WIDTH: f32 = 12.0;
+// the nodes view draws every node as a square this wide in world units, pinned and grabbed ones
+// standing out in their own colors, which the links touching them take in the nodes line colors
+const NODE_SIZE: f32 = 4.0;
+const NODE_COLOR: Vec4 = vec4(1.0, 1.0, 1.0, 1.0);
+const PINNED_NODE_COLOR: Vec4 = vec4(1.0, 0.3, 0.3, 1.0);
+const SELECTED_NODE_COLOR: Vec4 = vec4(1.0, 0.9, 0.2, 1.0);
+// air resistance of the flag outlines when toggled on
+const AIR_DRAG: f32 = 0.05;
+// above this many nodes the index overlay is unreadable clutter, so it is not drawn
+const INDEX_OVERLAY_MAX_NODES: usize = 2000;
+// stretch at which links of the demo flag tear, once tearing is enabled
+const TEAR_FACTOR: f32 = 1.6;
+// distance kept between the cloth and the window edges
+const BOUNDARY_MARGIN: f32 = 10.0;
+// grab radius in world units at a zoom of 1, it shrinks when zooming in to stay the same on screen
+const SELECT_RADIUS: f32 = 10.0;
+// camera motion per arrow key press at a zoom of 1, and zoom change per scroll wheel notch
+const PAN_STEP: f32 = 50.0;
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 8.0;
+// seconds between refreshes of the statistics HUD, so the numbers stay readable
+const HUD_REFRESH: f32 = 0.1;
+
+// the animation capture records every third frame at a quarter of the window size, for at most
+// 200 frames, so a recording stays around 10 seconds and a few megabytes
+const GIF_FILE: &str = "capture.gif";
+const GIF_DOWNSCALE: usize = 4;
+const GIF_FRAME_INTERVAL: usize = 3;
+const GIF_MAX_FRAMES: usize = 200;
+// full size frames written by the PNG sequence key, about 3 seconds
+const PNG_SEQUENCE_FRAMES: usize = 180;
+// file written by the parameter dump key, which `--params` can load back
+const PARAMS_FILE: &str = "params.ron";
+// simulation state written and read back by the save and load keys
+const STATE_FILE: &str = "state.json";
+// mesh written by the export key, for external renderers
+const OBJ_FILE: &str = "flag.obj";
+// camera framing kept from one run to the next
+const VIEW_FILE: &str = ".flags-view.ron";
+
+// Fixed `--bench` workload: the demo flag stepped 1000 times at the interactive sub-step dt.
+const BENCH_STEPS: usize = 1000;
+const METRICS_INTERVAL: usize = 100;
+// steps kept for scrubbing, 10 seconds of simulation
+const HISTORY_FRAMES: usize = 600;
+
+// command line arguments, clap shows the doc comments in `--help`
+#[derive(Parser)]
+#[command(about = "Flags waving in the wind")]
+struct Args {
+    /// Nodes along the width of each flag
+    #[arg(long, default_value_t = 50)]
+    width: usize,
+    /// Nodes along the height of each flag
+    #[arg(long, default_value_t = 30)]
+    height: usize,
+    /// Width of each flag in pixels
+    #[arg(long, default_value_t = 1000.0)]
+    size: f32,
+    /// Top left corner of the first flag, as X,Y
+    #[arg(long, value_parser = parse_corner, default_value = "100,100")]
+    corner: Vec2,
+    /// Number of flags, laid out in a grid
+    #[arg(long, default_value_t = 1)]
+    flags: usize,
+    /// Show a single flag in 3D, billowing in the wind around a sphere, from a fixed camera
+    #[arg(long = "3d", conflicts_with_all = ["config", "mask", "flags", "settle"])]
+    three_d: bool,
+    /// Silent steps run before the first frame, so the flags open already draped
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["config", "mask"])]
+    settle: usize,
+    /// Scene file describing the flags, gravity, wind and sub-steps, in TOML
+    #[arg(
+        long,
+        value_parser = SceneConfig::load,
+        conflicts_with_all = ["width", "height", "size", "corner", "flags", "mask"]
+    )]
+    config: Option<SceneConfig>,
+    /// Image whose opaque pixels become the nodes of a single flag, pure red ones pinned
+    #[arg(long, value_name = "PNG", conflicts_with_all = ["width", "height", "flags"])]
+    mask: Option<String>,
+    /// Background color, as R,G,B or R,G,B,A with channels in 0..1
+    #[arg(long, value_parser = parse_color)]
+    bg: Option<(f32, f32, f32, f32)>,
+    /// Settings file written by the dump key
+    #[arg(long, value_parser = load_settings)]
+    params: Option<Settings>,
+    /// Image drawn over the cloth in the textured view
+    #[arg(long, value_parser = load_texture)]
+    texture: Option<image::RgbaImage>,
+    /// Width of the links in pixels, 0 draws them as GL lines
+    #[arg(long, value_parser = parse_line_width, default_value_t = 0.0)]
+    line_width: f32,
+    /// Print the time spent in each phase of a step along with the fps
+    #[arg(long)]
+    timings: bool,
+    /// Time the solver on a fixed workload instead of opening a window
+    #[arg(long)]
+    bench: bool,
+    /// Print solver metrics while benchmarking
+    #[arg(long)]
+    metrics: bool,
+    /// Write a Chrome trace of the benchmark to FILE
+    #[arg(long, value_name = "FILE")]
+    trace_chrome: Option<String>,
+}
+
+// spacing of the flags from `--flags` relative to their extent
+const FLAG_GRID_GAP: f32 = 1.2;
+
+// everything tunable from the demo, saved and loaded as RON
+#[derive(Clone, Serialize, Deserialize)]
+struct Settings {
+    sub_steps: usize,
+    physics: Params,
+}
+
+impl Settings {
+    fn of(physics: &Physics) -> Settings {
+        Settings {
+            sub_steps: physics.sub_steps(),
+            physics: physics.params(),
+        }
+    }
+
+    fn apply(&self, physics: &mut Physics) {
+        physics.set_params(&self.physics);
+        physics.set_sub_steps(self.sub_steps);
+    }
+}
+
+// the camera, saved on quit and restored at launch
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct ViewState {
+    offset: Vec2,
+    scale: f32,
+}
+
+impl Default for ViewState {
+    fn default() -> ViewState {
+        ViewState {
+            offset: Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ViewState {
+    // a missing or malformed file falls back to the default view
+    fn load() -> ViewState {
+        fs::read_to_string(VIEW_FILE)
+            .ok()
+            .and_then(|text| ron::from_str::<ViewState>(&text).ok())
+            .filter(|view| view.offset.is_finite() && view.scale.is_finite())
+            .map(|view| ViewState {
+                scale: view.scale.clamp(MIN_ZOOM, MAX_ZOOM),
+                ..view
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| fs::write(VIEW_FILE, text).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("could not write {}: {}", VIEW_FILE, e);
+        }
+    }
+}
+
+// what the colors of the line view show
+#[derive(Clone, Copy, PartialEq)]
+enum LineColors {
+    Plain,
+    Strain,
+    Speed,
+    // links touching a grabbed or pinned node
+    Nodes,
+}
 
 enum UpdateCommand {
     OneFrame,
@@ -26,48 +250,377 @@ enum UpdateCommand {
 struct Stage {
     pipeline: Pipeline,
     bindings: Bindings,
+    clear_color: (f32, f32, f32, f32),
+    strain_bindings: Bindings,
+    speed_bindings: Bindings,
+    line_colors: LineColors,
+    // links are drawn as quads this wide in world units, or as GL lines when 0
+    line_width: f32,
+    thick_bindings: Bindings,
+    // a pole drawn along the pinned nodes of each flag
+    show_pole: bool,
+    pole_bindings: Bindings,
+    num_poles: i32,
+    // a square at each node over the links, colored by whether it is pinned or grabbed
+    show_nodes: bool,
+    node_bindings: Bindings,
+    triangle_pipeline: Pipeline,
+    exploded_bindings: Bindings,
+    num_exploded_indices: i32,
+    show_exploded: bool,
+    filled_pipeline: Pipeline,
+    show_filled: bool,
+    textured_pipeline: Pipeline,
+    textured_bindings: Bindings,
+    num_triangle_indices: i32,
+    show_textured: bool,
+    indices: Vec<u32>,
+    text: TextRenderer,
+    // drawn in window space, unaffected by the camera
+    hud_text: TextRenderer,
+    show_indices: bool,
+    show_hud: bool,
+    hud: Vec<String>,
+    hud_refresh: Instant,
+    hud_frames: usize,
 
     physics: Physics,
     last_frame: Instant,
     frame_count: usize,
     mouse_pressed: bool,
+    // dragging with alt held cuts the links crossed by the cursor instead of grabbing
+    alt_held: bool,
+    cutting: bool,
     mouse_pos: Vec2,
     last_mouse_pos: Vec2,
+    // last mouse position in window pixels
+    cursor: Vec2,
+    // world position shown at the top left corner of the window, and its magnification
+    camera_offset: Vec2,
+    camera_scale: f32,
+    panning: bool,
+    // window size in logical pixels, which is also the extent of the world at a zoom of 1
+    view_size: Vec2,
     can_update: UpdateCommand,
     accumulate_time: u128,
+    // print the time spent in each phase of a step with the fps
+    timings: bool,
+    sim_accumulator: f32,
+    // simulated seconds per real second
+    time_scale: f32,
+    interpolate: bool,
+    prev_points: Vec<Vec2>,
+    // the positions drawn this frame, kept to reuse the allocation
+    points: Vec<Vec2>,
+    inflated: bool,
+    air_drag: bool,
+    wind: Vec<WindId>,
+    waving: bool,
+    // positions of the nodes and time when the ribbon wave started, which moves the pinned ones
+    ribbon: Option<(Vec<Vec2>, f32)>,
+    stitched: bool,
+    cambered: bool,
+    tearable: bool,
+    self_collision: bool,
+    capture: Option<GifCapture>,
+    png_capture: Option<PngCapture>,
+    history: History,
+    obstacle: Option<ObstacleId>,
+    dragging_obstacle: bool,
+    obstacle_bindings: Bindings,
 }
 
-impl Stage {
-    pub fn new(ctx: &mut Context) -> Stage {
-        quad_rand::srand(1);
+// the world seen through a window of `view_size` with its top left corner at `offset`, y down
+fn world_projection(offset: Vec2, view_size: Vec2, scale: f32) -> Mat4 {
+    let max = offset + view_size / scale;
+    Mat4::orthographic_lh(offset.x, max.x, max.y, offset.y, 0.0, 1.0)
+}
 
-        let physics = Physics::new(&[FlagParams {
-            corner: vec2(100.0, 100.0),
-            size: 1000.0,
-            width: 50,
-            height: 30,
-        }]);
+// the world point drawn by `proj` at `pos` of a screen of `screen_size`
+fn screen_to_world(proj: Mat4, screen_size: Vec2, pos: Vec2) -> Vec2 {
+    let ndc = vec2(
+        pos.x / screen_size.x * 2.0 - 1.0,
+        1.0 - pos.y / screen_size.y * 2.0,
+    );
+    proj.inverse().project_point3(ndc.extend(0.0)).truncate()
+}
+
+// takes as many whole fixed steps out of the accumulator as it holds, the remainder is carried
+// over to the next frame
+fn fixed_steps(accumulator: &mut f32, dt: f32) -> usize {
+    let mut steps = 0;
+    while *accumulator >= dt {
+        *accumulator -= dt;
+        steps += 1;
+    }
+    steps
+}
+
+// fraction of a fixed step left in the accumulator, used to blend the last two physics states
+fn interpolation_alpha(accumulator: f32, dt: f32) -> f32 {
+    (accumulator / dt).clamp(0.0, 1.0)
+}
+
+// blue for a node at rest, red once it moves `MAX_DISPLAY_SPEED` per step
+fn speed_color(speed: f32) -> Vec4 {
+    let t = (speed / MAX_DISPLAY_SPEED).clamp(0.0, 1.0);
+    vec4(t, 0.2, 1.0 - t, 1.0)
+}
+
+// green for a relaxed link, red once it is stretched by `MAX_DISPLAY_STRAIN`
+fn strain_color(strain: f32) -> Vec4 {
+    let t = (strain / MAX_DISPLAY_STRAIN).clamp(0.0, 1.0);
+    vec4(t, 1.0 - t, 0.0, 1.0)
+}
+
+// `strain_color` for both ends of every link, in the order of `Physics::get_indices`
+fn strain_colors(physics: &Physics) -> Vec<Vec4> {
+    physics
+        .link_strains()
+        .into_iter()
+        .flat_map(|strain| [strain_color(strain); 2])
+        .collect()
+}
+
+// node positions and colors drawn as lines through the link indices, the index buffer streamed
+// at `Physics::link_capacity` so tears, cuts and mends only re-upload `indices`
+fn line_bindings(ctx: &mut Context, physics: &Physics, indices: &[u32]) -> Bindings {
+    let num_nodes = physics.num_nodes();
+    let index_buffer = Buffer::index_stream(
+        ctx,
+        IndexType::Int,
+        physics.link_capacity() * 2 * std::mem::size_of::<u32>(),
+    );
+    index_buffer.update(ctx, indices);
+    Bindings {
+        vertex_buffers: vec![
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_nodes * std::mem::size_of::<Vec2>(),
+            ),
+            Buffer::immutable(ctx, BufferType::VertexBuffer, &vec![CLOTH_COLOR; num_nodes]),
+        ],
+        index_buffer,
+        images: vec![],
+    }
+}
+
+// the line bindings with a color per node streamed every frame, for the speed view
+fn speed_bindings(ctx: &mut Context, bindings: &Bindings, num_nodes: usize) -> Bindings {
+    Bindings {
+        vertex_buffers: vec![
+            bindings.vertex_buffers[0],
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_nodes * std::mem::size_of::<Vec4>(),
+            ),
+        ],
+        index_buffer: bindings.index_buffer,
+        images: vec![],
+    }
+}
+
+// quads with their positions and colors streamed every frame
+fn quad_bindings(ctx: &mut Context, num_quads: usize) -> Bindings {
+    let indices = (0..num_quads as u32)
+        .flat_map(|q| [4 * q, 4 * q + 1, 4 * q + 2, 4 * q, 4 * q + 2, 4 * q + 3])
+        .collect::<Vec<u32>>();
+    Bindings {
+        vertex_buffers: vec![
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_quads * 4 * std::mem::size_of::<Vec2>(),
+            ),
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_quads * 4 * std::mem::size_of::<Vec4>(),
+            ),
+        ],
+        index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &indices),
+        images: vec![],
+    }
+}
+
+// For each flag with pinned nodes, a pole running down their left side from just above the
+// highest one to `ground`. `points` are the positions of all the nodes.
+fn pole_quads(physics: &Physics, points: &[Vec2], ground: f32) -> Vec<[Vec2; 4]> {
+    physics
+        .flags()
+        .scan(0, |offset, (width, height)| {
+            let nodes = *offset..*offset + width * height;
+            *offset = nodes.end;
+            Some(nodes)
+        })
+        .filter_map(|nodes| {
+            let (left, top) = nodes
+                .filter(|&i| physics.is_pinned(i))
+                .map(|i| points[i])
+                .fold(None, |acc: Option<(f32, f32)>, p| match acc {
+                    Some((x, y)) => Some((x.min(p.x), y.min(p.y))),
+                    None => Some((p.x, p.y)),
+                })?;
+            let x = left - POLE_WIDTH * 0.5;
+            Some(link_quad(
+                vec2(x, top - POLE_WIDTH),
+                vec2(x, ground),
+                POLE_WIDTH,
+            ))
+        })
+        .collect()
+}
+
+// the corners of a `width` wide quad along the segment from `a` to `b`
+fn link_quad(a: Vec2, b: Vec2, width: f32) -> [Vec2; 4] {
+    let side = (b - a).perp().normalize_or_zero() * (width * 0.5);
+    [a + side, b + side, b - side, a - side]
+}
+
+// the strain view gives every link its own pair of vertices so each can have its color, room
+// is made for `num_links` of them
+fn strain_bindings(ctx: &mut Context, num_links: usize) -> Bindings {
+    Bindings {
+        vertex_buffers: vec![
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_links * 2 * std::mem::size_of::<Vec2>(),
+            ),
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                num_links * 2 * std::mem::size_of::<Vec4>(),
+            ),
+        ],
+        index_buffer: Buffer::immutable(
+            ctx,
+            BufferType::IndexBuffer,
+            &(0..num_links as u32 * 2).collect::<Vec<u32>>(),
+        ),
+        images: vec![],
+    }
+}
 
-        let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &physics.get_indices());
+// the cloth as textured triangles, sharing the node positions of the line bindings
+fn textured_bindings(
+    ctx: &mut Context,
+    physics: &Physics,
+    positions: Buffer,
+    texture: Texture,
+) -> (Bindings, i32) {
+    let indices = physics.get_triangle_indices();
+    let bindings = Bindings {
+        vertex_buffers: vec![
+            positions,
+            Buffer::immutable(ctx, BufferType::VertexBuffer, &physics.get_uvs()),
+        ],
+        index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &indices),
+        images: vec![texture],
+    };
+    (bindings, indices.len() as i32)
+}
 
-        let positions_vertex_buffer = Buffer::stream(
+// a fan of triangles around the center vertex, the vertices are streamed as the obstacle moves
+fn obstacle_bindings(ctx: &mut Context) -> Bindings {
+    let n = OBSTACLE_SEGMENTS as u32;
+    let indices = (0..n)
+        .flat_map(|i| [0, 1 + i, 1 + (i + 1) % n])
+        .collect::<Vec<u32>>();
+    Bindings {
+        vertex_buffers: vec![Buffer::stream(
             ctx,
             BufferType::VertexBuffer,
-            physics.get_points().len() * std::mem::size_of::<Vec2>(),
-        );
+            (OBSTACLE_SEGMENTS + 1) * std::mem::size_of::<Vec2>(),
+        )],
+        index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &indices),
+        images: vec![],
+    }
+}
 
-        let bindings = Bindings {
-            vertex_buffers: vec![positions_vertex_buffer],
-            index_buffer,
-            images: vec![],
-        };
+fn obstacle_vertices(obstacle: Obstacle) -> Vec<Vec2> {
+    let step = std::f32::consts::TAU / OBSTACLE_SEGMENTS as f32;
+    std::iter::once(obstacle.center)
+        .chain(
+            (0..OBSTACLE_SEGMENTS)
+                .map(|i| obstacle.center + Vec2::from_angle(i as f32 * step) * obstacle.radius),
+        )
+        .collect()
+}
+
+// a blue, white and red tricolor, used when no texture is given
+fn default_texture(ctx: &mut Context) -> Texture {
+    let pixels = [0, 85, 164, 255, 255, 255, 255, 255, 239, 65, 53, 255];
+    let texture = Texture::from_rgba8(ctx, 3, 1, &pixels);
+    texture.set_filter(ctx, FilterMode::Nearest);
+    texture
+}
+
+fn exploded_bindings(ctx: &mut Context, physics: &Physics) -> (Bindings, i32) {
+    let (vertices, indices) = physics.get_exploded_triangles(EXPLODE_SHRINK);
+    let bindings = Bindings {
+        vertex_buffers: vec![
+            Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                vertices.len() * std::mem::size_of::<Vec2>(),
+            ),
+            Buffer::immutable(
+                ctx,
+                BufferType::VertexBuffer,
+                &vec![CLOTH_COLOR; vertices.len()],
+            ),
+        ],
+        index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &indices),
+        images: vec![],
+    };
+    (bindings, indices.len() as i32)
+}
+
+impl Stage {
+    pub fn new(
+        ctx: &mut Context,
+        mut physics: Physics,
+        clear_color: (f32, f32, f32, f32),
+        settings: Option<Settings>,
+        texture: Option<image::RgbaImage>,
+        line_width: f32,
+        timings: bool,
+    ) -> Result<Stage, String> {
+        quad_rand::srand(1);
+
+        setup_physics(&mut physics, vec2(WIDTH as f32, HEIGHT as f32));
+        physics.set_step_timing(timings);
+        let view = ViewState::load();
+        physics.set_select_radius(SELECT_RADIUS / view.scale);
+        if let Some(settings) = &settings {
+            settings.apply(&mut physics);
+        }
+        let inflated = physics.params().pressure[0] != 0.0;
+        let air_drag = physics.params().drag.iter().any(|&d| d != 0.0);
 
-        let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
+        let indices = physics.get_indices();
+        let bindings = line_bindings(ctx, &physics, &indices);
+        let strain_bindings = strain_bindings(ctx, physics.link_capacity());
+        let speed_bindings = speed_bindings(ctx, &bindings, physics.num_nodes());
+
+        let shader = shader::compile(
+            ctx,
+            "line",
+            shader::VERTEX,
+            shader::FRAGMENT,
+            shader::meta(),
+        )?;
 
         let pipeline = Pipeline::with_params(
             ctx,
-            &[BufferLayout::default()],
-            &[VertexAttribute::new("pos", VertexFormat::Float2)],
+            &[BufferLayout::default(), BufferLayout::default()],
+            &[
+                VertexAttribute::with_buffer("pos", VertexFormat::Float2, 0),
+                VertexAttribute::with_buffer("color0", VertexFormat::Float4, 1),
+            ],
             shader,
             PipelineParams {
                 primitive_type: PrimitiveType::Lines,
@@ -75,17 +628,356 @@ impl Stage {
             },
         );
 
-        Stage {
+        let triangle_pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default(), BufferLayout::default()],
+            &[
+                VertexAttribute::with_buffer("pos", VertexFormat::Float2, 0),
+                VertexAttribute::with_buffer("color0", VertexFormat::Float4, 1),
+            ],
+            shader,
+            PipelineParams {
+                primitive_type: PrimitiveType::Triangles,
+                ..Default::default()
+            },
+        );
+
+        // the filled view shares the exploded triangles, only reading their positions
+        let filled_shader = shader::compile(
+            ctx,
+            "filled",
+            shader::FILLED_VERTEX,
+            shader::FILLED_FRAGMENT,
+            shader::filled_meta(),
+        )?;
+        let filled_pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[VertexAttribute::with_buffer("pos", VertexFormat::Float2, 0)],
+            filled_shader,
+            PipelineParams {
+                primitive_type: PrimitiveType::Triangles,
+                ..Default::default()
+            },
+        );
+
+        let (exploded_bindings, num_exploded_indices) = exploded_bindings(ctx, &physics);
+
+        let texture = match texture {
+            Some(image) => {
+                let (width, height) = (image.width() as u16, image.height() as u16);
+                Texture::from_rgba8(ctx, width, height, &image.into_raw())
+            }
+            None => default_texture(ctx),
+        };
+        let textured_shader = shader::compile(
+            ctx,
+            "textured",
+            shader::TEXTURED_VERTEX,
+            shader::TEXTURED_FRAGMENT,
+            shader::textured_meta(),
+        )?;
+        let textured_pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default(), BufferLayout::default()],
+            &[
+                VertexAttribute::with_buffer("pos", VertexFormat::Float2, 0),
+                VertexAttribute::with_buffer("uv", VertexFormat::Float2, 1),
+            ],
+            textured_shader,
+            PipelineParams {
+                primitive_type: PrimitiveType::Triangles,
+                ..Default::default()
+            },
+        );
+        let (textured_bindings, num_triangle_indices) =
+            textured_bindings(ctx, &physics, bindings.vertex_buffers[0], texture);
+
+        let text = TextRenderer::new(ctx)?;
+        let hud_text = TextRenderer::new(ctx)?;
+        let prev_points = physics.get_points();
+
+        Ok(Stage {
             pipeline,
             bindings,
+            clear_color,
+            strain_bindings,
+            speed_bindings,
+            line_colors: LineColors::Plain,
+            line_width,
+            thick_bindings: quad_bindings(ctx, physics.link_capacity()),
+            show_pole: false,
+            pole_bindings: quad_bindings(ctx, physics.flags().count()),
+            num_poles: 0,
+            show_nodes: false,
+            node_bindings: quad_bindings(ctx, physics.num_nodes()),
+            triangle_pipeline,
+            exploded_bindings,
+            num_exploded_indices,
+            show_exploded: false,
+            filled_pipeline,
+            show_filled: false,
+            textured_pipeline,
+            textured_bindings,
+            num_triangle_indices,
+            show_textured: false,
+            indices,
+            text,
+            hud_text,
+            show_indices: false,
+            show_hud: false,
+            hud: vec![],
+            hud_refresh: Instant::now(),
+            hud_frames: 0,
             physics,
             last_frame: Instant::now(),
             frame_count: 0,
             mouse_pressed: false,
+            alt_held: false,
+            cutting: false,
             mouse_pos: Vec2::ZERO,
             last_mouse_pos: Vec2::ZERO,
+            cursor: Vec2::ZERO,
+            camera_offset: view.offset,
+            camera_scale: view.scale,
+            panning: false,
+            view_size: vec2(WIDTH as f32, HEIGHT as f32),
             can_update: UpdateCommand::Continue,
             accumulate_time: 0,
+            timings,
+            sim_accumulator: 0.0,
+            time_scale: 1.0,
+            interpolate: true,
+            prev_points,
+            points: vec![],
+            inflated,
+            air_drag,
+            wind: vec![],
+            waving: false,
+            ribbon: None,
+            stitched: false,
+            cambered: false,
+            tearable: false,
+            self_collision: false,
+            capture: None,
+            png_capture: None,
+            history: History::new(HISTORY_FRAMES),
+            obstacle: None,
+            dragging_obstacle: false,
+            obstacle_bindings: obstacle_bindings(ctx),
+        })
+    }
+
+    // recreates every buffer sized by the nodes or links, after the cloth topology changed
+    fn rebuild_buffers(&mut self, ctx: &mut Context) {
+        for b in [
+            &self.bindings,
+            &self.strain_bindings,
+            &self.exploded_bindings,
+            &self.thick_bindings,
+            &self.pole_bindings,
+            &self.node_bindings,
+        ] {
+            b.vertex_buffers.iter().for_each(|v| v.delete());
+            b.index_buffer.delete();
+        }
+        // the positions are shared with `bindings` and the texture is kept
+        self.textured_bindings.vertex_buffers[1].delete();
+        self.textured_bindings.index_buffer.delete();
+        self.speed_bindings.vertex_buffers[1].delete();
+        self.physics.write_indices(&mut self.indices);
+        self.bindings = line_bindings(ctx, &self.physics, &self.indices);
+        self.strain_bindings = strain_bindings(ctx, self.physics.link_capacity());
+        self.speed_bindings = speed_bindings(ctx, &self.bindings, self.physics.num_nodes());
+        self.thick_bindings = quad_bindings(ctx, self.physics.link_capacity());
+        self.pole_bindings = quad_bindings(ctx, self.physics.flags().count());
+        self.node_bindings = quad_bindings(ctx, self.physics.num_nodes());
+        (self.exploded_bindings, self.num_exploded_indices) = exploded_bindings(ctx, &self.physics);
+        (self.textured_bindings, self.num_triangle_indices) = textured_bindings(
+            ctx,
+            &self.physics,
+            self.bindings.vertex_buffers[0],
+            self.textured_bindings.images[0],
+        );
+        if self.prev_points.len() != self.physics.num_nodes() {
+            self.prev_points = self.physics.get_points();
+            // the ribbon starting positions belong to the previous nodes
+            self.ribbon = None;
+        }
+    }
+
+    // After links were torn, cut or mended the link buffers, which have room for every link, only
+    // need the new indices, and the triangles need the torn cells left out. Stitches and merges
+    // add links beyond that room, which takes a full rebuild.
+    fn update_links(&mut self, ctx: &mut Context) {
+        if self.physics.link_capacity() * 2 * std::mem::size_of::<u32>()
+            > self.bindings.index_buffer.size()
+        {
+            self.rebuild_buffers(ctx);
+            return;
+        }
+        self.physics.write_indices(&mut self.indices);
+        self.bindings.index_buffer.update(ctx, &self.indices);
+        for b in [&self.exploded_bindings, &self.textured_bindings] {
+            b.index_buffer.delete();
+        }
+        self.exploded_bindings
+            .vertex_buffers
+            .iter()
+            .for_each(|v| v.delete());
+        self.textured_bindings.vertex_buffers[1].delete();
+        (self.exploded_bindings, self.num_exploded_indices) = exploded_bindings(ctx, &self.physics);
+        (self.textured_bindings, self.num_triangle_indices) = textured_bindings(
+            ctx,
+            &self.physics,
+            self.bindings.vertex_buffers[0],
+            self.textured_bindings.images[0],
+        );
+    }
+
+    // world space shown in the window
+    fn projection(&self) -> Mat4 {
+        world_projection(self.camera_offset, self.view_size, self.camera_scale)
+    }
+
+    // Maps a window position given by the mouse events to world space. Both the events and
+    // `screen_size` are in physical pixels, which differ from the logical ones on high dpi screens.
+    fn to_world(&self, ctx: &Context, x: f32, y: f32) -> Vec2 {
+        let (width, height) = ctx.screen_size();
+        screen_to_world(self.projection(), vec2(width, height), vec2(x, y))
+    }
+
+    // zooms around the cursor, keeping the world point under it in place
+    fn zoom(&mut self, ctx: &Context, factor: f32) {
+        let anchor = self.to_world(ctx, self.cursor.x, self.cursor.y);
+        self.camera_scale = (self.camera_scale * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.camera_offset += anchor - self.to_world(ctx, self.cursor.x, self.cursor.y);
+        self.physics
+            .set_select_radius(SELECT_RADIUS / self.camera_scale);
+    }
+
+    // draws the cloth and the text into the current pass, from buffers already updated
+    fn draw_scene(&mut self, ctx: &mut Context, proj: Mat4) {
+        if self.show_textured {
+            ctx.apply_pipeline(&self.textured_pipeline);
+            ctx.apply_bindings(&self.textured_bindings);
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.num_triangle_indices, 1);
+        } else if self.show_filled {
+            ctx.apply_pipeline(&self.filled_pipeline);
+            ctx.apply_bindings(&self.exploded_bindings);
+            ctx.apply_uniforms(&shader::FilledUniforms {
+                mvp: proj,
+                front_color: CLOTH_COLOR,
+                back_color: CLOTH_BACK_COLOR,
+            });
+            ctx.draw(0, self.num_exploded_indices, 1);
+        } else if self.show_exploded {
+            ctx.apply_pipeline(&self.triangle_pipeline);
+            ctx.apply_bindings(&self.exploded_bindings);
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.num_exploded_indices, 1);
+        } else if self.line_width > 0.0 {
+            ctx.apply_pipeline(&self.triangle_pipeline);
+            ctx.apply_bindings(&self.thick_bindings);
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.physics.num_links() * 6, 1);
+        } else {
+            ctx.apply_pipeline(&self.pipeline);
+            ctx.apply_bindings(match self.line_colors {
+                LineColors::Plain => &self.bindings,
+                LineColors::Strain | LineColors::Nodes => &self.strain_bindings,
+                LineColors::Speed => &self.speed_bindings,
+            });
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.physics.num_links() * 2, 1);
+        }
+        if self.show_nodes {
+            ctx.apply_pipeline(&self.triangle_pipeline);
+            ctx.apply_bindings(&self.node_bindings);
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.physics.num_nodes() as i32 * 6, 1);
+        }
+        if self.show_pole && self.num_poles > 0 {
+            ctx.apply_pipeline(&self.triangle_pipeline);
+            ctx.apply_bindings(&self.pole_bindings);
+            ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
+            ctx.draw(0, self.num_poles * 6, 1);
+        }
+        if self.obstacle.is_some() {
+            ctx.apply_pipeline(&self.filled_pipeline);
+            ctx.apply_bindings(&self.obstacle_bindings);
+            ctx.apply_uniforms(&shader::FilledUniforms {
+                mvp: proj,
+                front_color: OBSTACLE_COLOR,
+                back_color: OBSTACLE_COLOR,
+            });
+            ctx.draw(0, OBSTACLE_SEGMENTS as i32 * 3, 1);
+        }
+        self.text.draw(ctx, proj, vec4(1.0, 1.0, 0.4, 1.0));
+        let (width, height) = (self.view_size.x, self.view_size.y);
+        let screen = Mat4::orthographic_lh(0.0, width, height, 0.0, 0.0, 1.0);
+        self.hud_text.draw(ctx, screen, vec4(1.0, 1.0, 0.4, 1.0));
+    }
+
+    // the colors of both ends of every link, for the views coloring each link as a whole
+    fn link_colors(&self) -> Vec<Vec4> {
+        if self.line_colors == LineColors::Nodes {
+            self.physics
+                .link_highlights()
+                .into_iter()
+                .flat_map(|highlight| {
+                    let color = match highlight {
+                        LinkHighlight::None => CLOTH_COLOR,
+                        LinkHighlight::Pinned => PINNED_NODE_COLOR,
+                        LinkHighlight::Selected => SELECTED_NODE_COLOR,
+                    };
+                    [color; 2]
+                })
+                .collect()
+        } else {
+            strain_colors(&self.physics)
+        }
+    }
+
+    // writes the next `num_frames` drawn frames as PNG files named after `name` and the time
+    fn start_png_capture(&mut self, ctx: &mut Context, name: &str, num_frames: usize) {
+        if let Some(capture) = self.png_capture.take() {
+            capture.finish(ctx);
+        }
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.png_capture = Some(PngCapture::new(
+            ctx,
+            self.view_size.x as u32,
+            self.view_size.y as u32,
+            format!("{}-{}", name, stamp),
+            num_frames,
+        ));
+    }
+
+    fn stop_capture(&mut self, ctx: &mut Context) {
+        if let Some(capture) = self.capture.take() {
+            println!("encoding {} frames", capture.num_frames());
+            capture.finish(ctx, GIF_FILE.to_string(), DT);
+        }
+    }
+
+    fn dump_settings(&self) {
+        let text =
+            match ron::ser::to_string_pretty(&Settings::of(&self.physics), Default::default()) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("could not serialize the parameters: {}", e);
+                    return;
+                }
+            };
+        println!("{}", text);
+        match fs::write(PARAMS_FILE, text) {
+            Ok(()) => println!("parameters written to {}", PARAMS_FILE),
+            Err(e) => eprintln!("could not write {}: {}", PARAMS_FILE, e),
         }
     }
 }
@@ -95,6 +987,11 @@ impl EventHandler for Stage {
         match self.can_update {
             UpdateCommand::Stop => return,
             UpdateCommand::Quit => {
+                ViewState {
+                    offset: self.camera_offset,
+                    scale: self.camera_scale,
+                }
+                .save();
                 ctx.quit();
                 return;
             }
@@ -102,26 +999,67 @@ impl EventHandler for Stage {
         }
 
         let start = Instant::now();
-        let dt = 1. / 60.;
 
-        // update particle positions
-        for _ in 0..SUB_STEPS {
-            self.physics.step(vec2(200.0, 50.0), dt / SUB_STEPS as f32);
+        self.sim_accumulator += self.last_frame.elapsed().as_secs_f32().min(MAX_FRAME_TIME);
+        if let UpdateCommand::OneFrame = self.can_update {
+            self.sim_accumulator = DT;
         }
 
-        if self.mouse_pressed {
-            self.physics.move_selected_nodes(self.mouse_pos);
+        // run as many fixed steps as fit in the elapsed time
+        for _ in 0..fixed_steps(&mut self.sim_accumulator, DT) {
+            self.physics.write_points(&mut self.prev_points);
+
+            if let Some((start, t0)) = &self.ribbon {
+                self.physics.drive_pinned(
+                    |i, t| start[i] + vec2(0.0, 40.0 * (3.0 * (t - t0) - start[i].x / 100.0).sin()),
+                    self.physics.time(),
+                );
+            }
+
+            // update particle positions
+            // fast forward runs more sub-steps rather than longer ones, which would be unstable
+            let splits = self.time_scale.ceil() as usize;
+            for _ in 0..splits {
+                self.physics.advance(DT * self.time_scale / splits as f32);
+            }
+
+            if self.mouse_pressed {
+                self.physics.move_selected_nodes(self.mouse_pos);
+            }
+            self.history.push(&self.physics);
+        }
+        if self.physics.take_links_changed() {
+            self.update_links(ctx);
         }
 
         self.frame_count += 1;
         self.accumulate_time += self.last_frame.elapsed().as_micros();
-        if self.frame_count % 120 == 0 {
+        if self.frame_count.is_multiple_of(120) {
             println!(
                 "fps: {}, time to update: {}",
                 1000000 / (self.accumulate_time / 120),
                 start.elapsed().as_micros()
             );
             self.accumulate_time = 0;
+            let stats = self.physics.stats();
+            println!(
+                "energy: {:.3}, speed: {:.3}-{:.3}, strain: {:.4}, outside: {}, sub-steps: {}",
+                stats.kinetic_energy,
+                stats.min_speed,
+                stats.max_speed,
+                stats.mean_strain,
+                stats.out_of_bounds,
+                stats.sub_steps
+            );
+            if self.timings {
+                let phases = self
+                    .physics
+                    .take_step_timings()
+                    .per_step()
+                    .map(|(name, d)| format!("{}={}us", name, d.as_micros()))
+                    .collect::<Vec<String>>();
+                println!("per step: {}", phases.join(" "));
+            }
         }
         self.last_frame = Instant::now();
 
@@ -130,28 +1068,106 @@ impl EventHandler for Stage {
         }
     }
 
-    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
+    // the view and the walls of the simulation follow the window
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.view_size = vec2(width, height) / ctx.dpi_scale();
+        self.physics
+            .set_bounds(WorldBounds::from_size(self.view_size));
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        if self.panning {
+            let grabbed = self.to_world(ctx, self.cursor.x, self.cursor.y);
+            self.camera_offset += grabbed - self.to_world(ctx, x, y);
+        }
+        self.cursor = vec2(x, y);
         self.last_mouse_pos = self.mouse_pos;
-        self.mouse_pos = vec2(x, y);
+        self.mouse_pos = self.to_world(ctx, x, y);
+        if let (true, Some(id)) = (self.dragging_obstacle, self.obstacle) {
+            self.physics.move_obstacle(id, self.mouse_pos);
+        }
+        if self.cutting {
+            self.physics.cut_links(self.last_mouse_pos, self.mouse_pos);
+            // rebuilt here too so cutting shows while paused
+            if self.physics.take_links_changed() {
+                self.update_links(ctx);
+            }
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _: f32, y: f32) {
+        // a horizontal scroll has no vertical part, and 0.0 has a positive sign
+        if y == 0.0 {
+            return;
+        }
+        self.zoom(ctx, ZOOM_STEP.powf(y.signum()));
     }
 
-    fn mouse_button_down_event(&mut self, _: &mut Context, button: MouseButton, x: f32, y: f32) {
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        let pos = self.to_world(ctx, x, y);
+        if button == MouseButton::Middle {
+            self.cursor = vec2(x, y);
+            self.panning = true;
+        }
         if button == MouseButton::Left {
             self.last_mouse_pos = self.mouse_pos;
-            self.mouse_pos = vec2(x, y);
-            self.physics.select_nodes(self.mouse_pos);
-            self.mouse_pressed = true;
+            self.mouse_pos = pos;
+            // a click on the obstacle drags it rather than the cloth
+            let on_obstacle = self.obstacle.and_then(|id| self.physics.obstacle(id));
+            if self.alt_held {
+                self.cutting = true;
+            } else if on_obstacle.is_some_and(|o| o.center.distance(pos) < o.radius) {
+                self.dragging_obstacle = true;
+            } else {
+                self.physics.select_nodes(self.mouse_pos);
+                self.mouse_pressed = true;
+            }
+        }
+        if button == MouseButton::Right {
+            if let Some((flag, link, dist)) = self.physics.nearest_link(pos) {
+                println!(
+                    "link {} of flag {}, {:.1} px away, length {:.2}",
+                    link,
+                    flag,
+                    dist,
+                    self.physics.link_length(flag, link)
+                );
+            }
         }
     }
 
     fn mouse_button_up_event(&mut self, _: &mut Context, button: MouseButton, _: f32, _: f32) {
         if button == MouseButton::Left {
             self.mouse_pressed = false;
+            self.dragging_obstacle = false;
+            self.cutting = false;
+        }
+        if button == MouseButton::Middle {
+            self.panning = false;
         }
     }
 
-    fn key_down_event(&mut self, _: &mut Context, keycode: KeyCode, _: KeyMods, _: bool) {
+    fn key_up_event(&mut self, _: &mut Context, keycode: KeyCode, _: KeyMods) {
+        if let KeyCode::LeftAlt | KeyCode::RightAlt = keycode {
+            self.alt_held = false;
+        }
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, _: bool) {
+        let toggled_constraint = match keycode {
+            KeyCode::Key1 => Some(ConstraintKind::Structural),
+            KeyCode::Key2 => Some(ConstraintKind::Shear),
+            KeyCode::Key3 => Some(ConstraintKind::Bend),
+            _ => None,
+        };
+        if let Some(kind) = toggled_constraint {
+            let enabled = !self.physics.constraint_enabled(kind);
+            println!("{:?} constraints enabled: {}", kind, enabled);
+            self.physics.set_constraint_enabled(kind, enabled);
+        }
+
         match keycode {
+            KeyCode::LeftAlt | KeyCode::RightAlt => self.alt_held = true,
             KeyCode::N => self.can_update = UpdateCommand::OneFrame,
             KeyCode::Space => {
                 self.can_update = match self.can_update {
@@ -159,29 +1175,728 @@ impl EventHandler for Stage {
                     _ => UpdateCommand::Continue,
                 }
             }
+            // scrub through the recorded steps, which pauses the simulation
+            KeyCode::Comma | KeyCode::Period => {
+                self.can_update = UpdateCommand::Stop;
+                let offset = if keycode == KeyCode::Comma { -1 } else { 1 };
+                self.history.scrub(&mut self.physics, offset);
+            }
+            KeyCode::P => self.physics.set_pin_path(
+                0,
+                vec![vec2(100.0, 400.0), vec2(700.0, 200.0), vec2(1300.0, 400.0)],
+                5.0,
+            ),
+            KeyCode::U => {
+                if let Some(node) = self.physics.nearest_node(self.mouse_pos) {
+                    let pinned = self.physics.is_pinned(node);
+                    self.physics.set_pinned(node, !pinned);
+                }
+            }
+            KeyCode::I => {
+                self.inflated = !self.inflated;
+                self.physics
+                    .set_pressure(0, if self.inflated { 10.0 } else { 0.0 });
+            }
+            KeyCode::C => {
+                let mode = match self.physics.clamp_mode() {
+                    ClampMode::Total => ClampMode::Velocity,
+                    ClampMode::Velocity => ClampMode::Total,
+                };
+                println!("clamp mode: {:?}", mode);
+                self.physics.set_clamp_mode(mode);
+            }
+            KeyCode::F3 => {
+                let solver = match self.physics.link_solver() {
+                    LinkSolver::GaussSeidel => LinkSolver::Jacobi,
+                    LinkSolver::Jacobi => LinkSolver::GaussSeidel,
+                };
+                println!("link solver: {:?}", solver);
+                self.physics.set_link_solver(solver);
+            }
+            KeyCode::T => {
+                // hang the flag from its top edge, torn away from the pole in the middle
+                self.physics.pin_edge_span(0, Edge::Top, 0..20);
+                self.physics.pin_edge_span(0, Edge::Top, 30..50);
+            }
+            KeyCode::A => {
+                self.cambered = !self.cambered;
+                self.physics
+                    .set_camber(0, if self.cambered { 0.3 } else { 0.0 });
+            }
+            KeyCode::E => {
+                self.tearable = !self.tearable;
+                println!("tearing: {}", self.tearable);
+                self.physics.set_tear_factor(
+                    0,
+                    if self.tearable {
+                        TEAR_FACTOR
+                    } else {
+                        f32::INFINITY
+                    },
+                );
+            }
+            KeyCode::Q => {
+                self.self_collision = !self.self_collision;
+                println!("self-collision: {}", self.self_collision);
+                self.physics.enable_self_collision(self.self_collision);
+            }
+            KeyCode::LeftBracket | KeyCode::RightBracket => {
+                let sub_steps = self.physics.sub_steps();
+                self.physics
+                    .set_sub_steps(if keycode == KeyCode::LeftBracket {
+                        sub_steps - 1
+                    } else {
+                        sub_steps + 1
+                    });
+                println!("sub-steps: {}", self.physics.sub_steps());
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let factor = if keycode == KeyCode::PageUp { 2.0 } else { 0.5 };
+                self.time_scale = (self.time_scale * factor).clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+                println!("time scale: {}", self.time_scale);
+            }
+            KeyCode::Equal | KeyCode::Minus => {
+                let step = if keycode == KeyCode::Equal {
+                    STIFFNESS_STEP
+                } else {
+                    -STIFFNESS_STEP
+                };
+                let stiffness =
+                    (self.physics.params().stiffness[0] + step).clamp(STIFFNESS_STEP, 1.0);
+                println!("stiffness: {:.2}", stiffness);
+                self.physics.set_stiffness(0, stiffness);
+            }
+            KeyCode::K => {
+                // shake the top corner of the pole up and down like a hand waving the flag
+                self.waving = !self.waving;
+                if self.waving {
+                    let (start, t0) = (self.physics.get_points()[0], self.physics.time());
+                    self.physics.set_kinematic(0, move |t| {
+                        start + vec2(0.0, 60.0 * (1.0 - (4.0 * (t - t0)).cos()))
+                    });
+                } else {
+                    self.physics.clear_kinematic(0);
+                }
+            }
+            KeyCode::F7 => {
+                // a wave travelling along the pinned nodes, like a ribbon held by a moving hand
+                self.ribbon = match self.ribbon {
+                    Some(_) => None,
+                    None => Some((self.physics.get_points(), self.physics.time())),
+                };
+            }
+            KeyCode::F9 if !self.stitched => {
+                // sews the flags into one banner, each right edge to the left edge of the next
+                self.stitched = true;
+                for flag in 1..self.physics.flags().count() {
+                    if let Err(e) = self.physics.stitch(flag - 1, Edge::Right, flag, Edge::Left) {
+                        println!("not stitched: {}", e);
+                    }
+                }
+                self.rebuild_buffers(ctx);
+            }
+            KeyCode::L => self.interpolate = !self.interpolate,
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+                let dir = match keycode {
+                    KeyCode::Left => vec2(-1.0, 0.0),
+                    KeyCode::Right => vec2(1.0, 0.0),
+                    KeyCode::Up => vec2(0.0, -1.0),
+                    _ => vec2(0.0, 1.0),
+                };
+                if keymods.shift {
+                    // tilts the gravity of every flag, to swing them around
+                    let gravity = self.physics.params().gravity;
+                    for (flag, &g) in gravity.iter().enumerate() {
+                        let g = Vec2::from(g) + dir * GRAVITY_STEP;
+                        self.physics.set_gravity(flag, g);
+                        println!("flag {} gravity: ({:.0}, {:.0})", flag, g.x, g.y);
+                    }
+                } else {
+                    self.camera_offset += dir * PAN_STEP / self.camera_scale;
+                }
+            }
+            KeyCode::R => {
+                self.physics.reset();
+                self.physics.write_points(&mut self.prev_points);
+            }
+            KeyCode::W => {
+                if self.wind.is_empty() {
+                    // a rippling breeze plus a localized gust in the middle of the flag
+                    self.physics.set_wind(
+                        vec2(150.0, 0.0),
+                        Some(Gust {
+                            amplitude: 0.8,
+                            frequency: 0.5,
+                            wavelength: 400.0,
+                        }),
+                    );
+                    self.wind = vec![self.physics.add_wind_field(WindField {
+                        force: vec2(0.0, -400.0),
+                        falloff: Falloff::Radial {
+                            center: vec2(600.0, 400.0),
+                            radius: 300.0,
+                        },
+                    })];
+                } else {
+                    self.physics.set_wind(Vec2::ZERO, None);
+                    for id in self.wind.drain(..) {
+                        self.physics.remove_wind_field(id);
+                    }
+                }
+            }
+            KeyCode::D => self.dump_settings(),
+            KeyCode::Z if keymods.ctrl => {
+                if self.physics.undo_grab() {
+                    self.physics.write_points(&mut self.prev_points);
+                    self.mouse_pressed = false;
+                } else {
+                    println!("no grab to undo");
+                }
+            }
+            KeyCode::Z => match self.obstacle.take() {
+                Some(id) => self.physics.remove_obstacle(id),
+                None => {
+                    self.obstacle = Some(self.physics.add_obstacle(Obstacle {
+                        center: self.mouse_pos,
+                        radius: OBSTACLE_RADIUS,
+                    }))
+                }
+            },
+            KeyCode::Y => match self.physics.save(STATE_FILE) {
+                Ok(()) => println!("simulation saved to {}", STATE_FILE),
+                Err(e) => eprintln!("could not write {}: {}", STATE_FILE, e),
+            },
+            KeyCode::F2 => match self.physics.export_obj(OBJ_FILE, 0.0) {
+                Ok(()) => println!("mesh exported to {}", OBJ_FILE),
+                Err(e) => eprintln!("could not write {}: {}", OBJ_FILE, e),
+            },
+            KeyCode::J => match Physics::load(STATE_FILE) {
+                Ok(mut physics) => {
+                    // the state holds the cloth only, the solver settings start over
+                    setup_physics(&mut physics, self.view_size);
+                    physics.set_sub_steps(self.physics.sub_steps());
+                    physics.set_select_radius(SELECT_RADIUS / self.camera_scale);
+                    physics.set_step_timing(self.timings);
+                    self.physics = physics;
+                    self.obstacle = None;
+                    self.rebuild_buffers(ctx);
+                    self.physics.write_points(&mut self.prev_points);
+                }
+                Err(e) => eprintln!("could not load {}: {}", STATE_FILE, e),
+            },
+            KeyCode::G => {
+                if self.capture.is_some() {
+                    self.stop_capture(ctx);
+                } else {
+                    println!("recording, press G again to stop");
+                    self.capture = Some(GifCapture::new(
+                        ctx,
+                        (self.view_size.x as usize / GIF_DOWNSCALE) as u16,
+                        (self.view_size.y as usize / GIF_DOWNSCALE) as u16,
+                        GIF_FRAME_INTERVAL,
+                        GIF_MAX_FRAMES,
+                    ));
+                }
+            }
+            KeyCode::F12 => self.start_png_capture(ctx, "screenshot", 1),
+            KeyCode::F11 => {
+                if let Some(capture) = self.png_capture.take() {
+                    println!("{} frames written", capture.num_written());
+                    capture.finish(ctx);
+                } else {
+                    println!("writing frames, press F11 again to stop");
+                    self.start_png_capture(ctx, "frame", PNG_SEQUENCE_FRAMES);
+                }
+            }
+            // soak the cloth under the cursor, which then moves as if heavy and wet
+            KeyCode::B => self.physics.set_damping_brush(self.mouse_pos, 80.0, 0.05),
+            KeyCode::M => {
+                // a second, lighter flag built on its own, hanging below the first one
+                let flag = FlagParams::builder()
+                    .corner(vec2(100.0, 800.0))
+                    .size(500.0)
+                    .grid(25, 15)
+                    .shear(true)
+                    .gravity(GRAVITY * 0.5)
+                    // weighted hem, the bottom rows give way less to the links
+                    .mass(1.0, 3.0)
+                    .damping(DAMPING)
+                    .stiffness(STIFFNESS)
+                    .max_push(MAX_PUSH)
+                    .build()
+                    .unwrap();
+                self.physics.merge(Physics::new(&[flag]));
+                self.rebuild_buffers(ctx);
+            }
+            KeyCode::H => self.show_hud = !self.show_hud,
+            KeyCode::S => {
+                self.line_colors = match self.line_colors {
+                    LineColors::Plain => LineColors::Strain,
+                    LineColors::Strain => LineColors::Speed,
+                    LineColors::Speed => LineColors::Nodes,
+                    LineColors::Nodes => LineColors::Plain,
+                }
+            }
+            KeyCode::X => self.show_exploded = !self.show_exploded,
+            KeyCode::F => self.show_filled = !self.show_filled,
+            KeyCode::V => self.show_textured = !self.show_textured,
+            KeyCode::F4 => self.show_pole = !self.show_pole,
+            KeyCode::F6 => self.show_nodes = !self.show_nodes,
+            KeyCode::F8 => {
+                let bounds = match self.physics.adaptive_sub_steps() {
+                    Some(_) => None,
+                    None => Some(ADAPTIVE_SUB_STEPS),
+                };
+                println!("adaptive sub-steps: {:?}", bounds);
+                self.physics.set_adaptive_sub_steps(bounds);
+            }
+            KeyCode::F5 => {
+                self.air_drag = !self.air_drag;
+                let drag = if self.air_drag { AIR_DRAG } else { 0.0 };
+                for flag in 0..self.physics.flags().count() {
+                    self.physics.set_drag(flag, drag);
+                }
+                println!("air drag: {}", drag);
+            }
+            KeyCode::O => {
+                self.show_indices = !self.show_indices;
+                if self.show_indices && self.physics.num_nodes() > INDEX_OVERLAY_MAX_NODES {
+                    println!(
+                        "index overlay is disabled above {} nodes",
+                        INDEX_OVERLAY_MAX_NODES
+                    );
+                }
+            }
             KeyCode::Escape => self.can_update = UpdateCommand::Quit,
             _ => (),
         }
     }
 
     fn draw(&mut self, ctx: &mut Context) {
-        self.bindings.vertex_buffers[0].update(ctx, &self.physics.get_points());
+        let mut points = std::mem::take(&mut self.points);
+        self.physics.write_points(&mut points);
+        if self.interpolate {
+            if let UpdateCommand::Continue = self.can_update {
+                let alpha = interpolation_alpha(self.sim_accumulator, DT);
+                points
+                    .iter_mut()
+                    .zip(&self.prev_points)
+                    .for_each(|(p, prev)| *p = prev.lerp(*p, alpha));
+            }
+        }
+        if self.line_colors == LineColors::Speed {
+            let colors = self
+                .physics
+                .node_speeds()
+                .into_iter()
+                .map(speed_color)
+                .collect::<Vec<Vec4>>();
+            self.speed_bindings.vertex_buffers[1].update(ctx, &colors);
+        }
+        if let LineColors::Strain | LineColors::Nodes = self.line_colors {
+            let segments = self
+                .indices
+                .iter()
+                .map(|&i| points[i as usize])
+                .collect::<Vec<Vec2>>();
+            let colors = self.link_colors();
+            self.strain_bindings.vertex_buffers[0].update(ctx, &segments);
+            self.strain_bindings.vertex_buffers[1].update(ctx, &colors);
+        }
+        if self.line_width > 0.0 {
+            let colors = match self.line_colors {
+                LineColors::Plain => vec![CLOTH_COLOR; self.indices.len()],
+                LineColors::Strain | LineColors::Nodes => self.link_colors(),
+                LineColors::Speed => {
+                    let speeds = self.physics.node_speeds();
+                    self.indices
+                        .iter()
+                        .map(|&i| speed_color(speeds[i as usize]))
+                        .collect()
+                }
+            };
+            let quads = self
+                .indices
+                .chunks_exact(2)
+                .flat_map(|l| {
+                    link_quad(
+                        points[l[0] as usize],
+                        points[l[1] as usize],
+                        self.line_width,
+                    )
+                })
+                .collect::<Vec<Vec2>>();
+            let quad_colors = colors
+                .chunks_exact(2)
+                .flat_map(|c| [c[0], c[1], c[1], c[0]])
+                .collect::<Vec<Vec4>>();
+            self.thick_bindings.vertex_buffers[0].update(ctx, &quads);
+            self.thick_bindings.vertex_buffers[1].update(ctx, &quad_colors);
+        }
+        // also read by the textured view
+        self.bindings.vertex_buffers[0].update(ctx, &points);
+        if self.show_exploded || self.show_filled {
+            let shrink = if self.show_exploded {
+                EXPLODE_SHRINK
+            } else {
+                0.0
+            };
+            let (vertices, _) = self.physics.get_exploded_triangles(shrink);
+            self.exploded_bindings.vertex_buffers[0].update(ctx, &vertices);
+        }
+        if self.show_nodes {
+            let half = vec2(NODE_SIZE * 0.5, 0.0);
+            // the gaps of a masked flag collapse to a point and disappear
+            let quads = points
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    if self.physics.is_absent(i) {
+                        [p; 4]
+                    } else {
+                        link_quad(p - half, p + half, NODE_SIZE)
+                    }
+                })
+                .collect::<Vec<[Vec2; 4]>>();
+            let mut colors = (0..points.len())
+                .map(|i| {
+                    if self.physics.is_pinned(i) {
+                        PINNED_NODE_COLOR
+                    } else {
+                        NODE_COLOR
+                    }
+                })
+                .collect::<Vec<Vec4>>();
+            for i in self.physics.selected_nodes() {
+                colors[i] = SELECTED_NODE_COLOR;
+            }
+            let colors = colors
+                .into_iter()
+                .flat_map(|c| [c; 4])
+                .collect::<Vec<Vec4>>();
+            self.node_bindings.vertex_buffers[0].update(ctx, &quads);
+            self.node_bindings.vertex_buffers[1].update(ctx, &colors);
+        }
+        if self.show_pole {
+            let poles = pole_quads(&self.physics, &points, self.view_size.y);
+            self.pole_bindings.vertex_buffers[0].update(ctx, &poles);
+            self.pole_bindings.vertex_buffers[1].update(ctx, &vec![POLE_COLOR; poles.len() * 4]);
+            self.num_poles = poles.len() as i32;
+        }
+        if let Some(obstacle) = self.obstacle.and_then(|id| self.physics.obstacle(id)) {
+            self.obstacle_bindings.vertex_buffers[0].update(ctx, &obstacle_vertices(obstacle));
+        }
 
-        let proj = Mat4::orthographic_lh(0.0, WIDTH as f32, HEIGHT as f32, 0.0, 0.0, 1.0);
+        self.hud_frames += 1;
+        let since_refresh = self.hud_refresh.elapsed().as_secs_f32();
+        if since_refresh >= HUD_REFRESH {
+            let (shown, recorded) = self.history.position();
+            let stats = self.physics.stats();
+            self.hud = vec![
+                format!("fps: {:.0}", self.hud_frames as f32 / since_refresh),
+                format!("steps: {}", self.physics.step_count()),
+                format!("sub-steps: {}", stats.sub_steps),
+                format!("sim time: {:.2}", self.physics.time()),
+                format!("time scale: {}", self.time_scale),
+                format!("nodes: {}", self.physics.num_nodes()),
+                format!("links: {}", self.physics.num_links()),
+                format!("torn links: {}", self.physics.num_torn_links()),
+                format!(
+                    "stretch: {:.4}",
+                    self.physics.total_current_length() / self.physics.total_resting_length()
+                ),
+                format!("kinetic energy: {:.3}", stats.kinetic_energy),
+                format!("speed: {:.3} to {:.3}", stats.min_speed, stats.max_speed),
+                format!("mean strain: {:.4}", stats.mean_strain),
+                format!("out of bounds: {}", stats.out_of_bounds),
+                format!("stability margin: {:.2}", self.physics.stability_margin()),
+                format!("history: {}/{}", shown, recorded),
+            ];
+            self.hud_refresh = Instant::now();
+            self.hud_frames = 0;
+        }
+
+        self.text.clear();
+        if self.show_indices && points.len() <= INDEX_OVERLAY_MAX_NODES {
+            for (i, p) in points.iter().enumerate() {
+                self.text.push_str(&i.to_string(), *p + vec2(2.0, 2.0), 1.0);
+            }
+        }
+        self.points = points;
+        self.hud_text.clear();
+        if self.show_hud {
+            for (i, line) in self.hud.iter().enumerate() {
+                self.hud_text
+                    .push_str(line, vec2(10.0, 10.0 + i as f32 * 24.0), 3.0);
+            }
+        }
+
+        let proj = self.projection();
+        let (r, g, b, a) = self.clear_color;
+
+        if let Some(capture) = &self.capture {
+            if capture.wants_frame() {
+                ctx.begin_pass(capture.pass(), PassAction::clear_color(r, g, b, a));
+                self.draw_scene(ctx, proj);
+                ctx.end_render_pass();
+            }
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.record();
+            if capture.is_full() {
+                self.stop_capture(ctx);
+            }
+        }
 
-        ctx.begin_default_pass(Default::default());
+        if let Some(pass) = self.png_capture.as_ref().map(PngCapture::pass) {
+            ctx.begin_pass(pass, PassAction::clear_color(r, g, b, a));
+            self.draw_scene(ctx, proj);
+            ctx.end_render_pass();
+        }
+        if let Some(capture) = &mut self.png_capture {
+            capture.record();
+            if capture.is_done() {
+                println!("{} frames written", capture.num_written());
+                self.png_capture.take().unwrap().finish(ctx);
+            }
+        }
 
-        ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings);
-        ctx.apply_uniforms(&shader::Uniforms { mvp: proj });
-        ctx.draw(0, self.physics.num_links() * 2, 1);
+        ctx.begin_default_pass(PassAction::clear_color(r, g, b, a));
+        self.draw_scene(ctx, proj);
         ctx.end_render_pass();
 
         ctx.commit_frame();
     }
 }
 
+// the interaction and walls of the demo, for a window of `view_size`
+fn setup_physics(physics: &mut Physics, view_size: Vec2) {
+    physics.set_bounds(WorldBounds::from_size(view_size));
+    physics.set_interaction_solve(4, 150.0);
+    physics.set_max_grab_step(40.0);
+    physics.set_grab_falloff_radius(80.0);
+    physics.set_boundary_margin(BOUNDARY_MARGIN);
+    physics.set_pin_check(cfg!(debug_assertions));
+}
+
+fn demo_flag() -> FlagParams {
+    demo_flag_at(vec2(100.0, 100.0), 1000.0, 50, 30).unwrap()
+}
+
+fn demo_flag_at(
+    corner: Vec2,
+    size: f32,
+    width: usize,
+    height: usize,
+) -> Result<FlagParams, String> {
+    FlagParams::builder()
+        .corner(corner)
+        .size(size)
+        .grid(width, height)
+        .shear(true)
+        .bend(false)
+        .gravity(GRAVITY)
+        .stiffness(STIFFNESS)
+        .max_push(MAX_PUSH)
+        .damping(DAMPING)
+        .build()
+}
+
+// a flag shaped like the image at `path`, one pixel per node, with the demo flag's settings
+fn mask_flag(path: &str, corner: Vec2, size: f32) -> Result<Physics, String> {
+    let mut physics = Physics::from_image_mask(path, corner, size)
+        .map_err(|e| format!("could not load the mask `{}`: {}", path, e))?;
+    physics.set_gravity(0, GRAVITY);
+    physics.set_stiffness(0, STIFFNESS);
+    physics.set_max_push(0, MAX_PUSH);
+    physics.set_damping(0, DAMPING);
+    Ok(physics)
+}
+
+// the flags asked for on the command line, in rows of up to the square root of their count
+fn demo_flags(args: &Args) -> Result<Vec<FlagParams>, String> {
+    if args.flags == 0 {
+        return Err("expected at least one flag".to_string());
+    }
+    let columns = (args.flags as f32).sqrt().ceil() as usize;
+    // the nodes of a flag are `size / width` apart
+    let spacing = args.size / args.width.max(1) as f32;
+    let extent = vec2(
+        spacing * args.width.saturating_sub(1) as f32,
+        spacing * args.height.saturating_sub(1) as f32,
+    ) * FLAG_GRID_GAP;
+    (0..args.flags)
+        .map(|i| {
+            let cell = vec2((i % columns) as f32, (i / columns) as f32);
+            demo_flag_at(
+                args.corner + cell * extent,
+                args.size,
+                args.width,
+                args.height,
+            )
+            .map(|flag| FlagParams {
+                settle_steps: args.settle,
+                ..flag
+            })
+        })
+        .collect()
+}
+
+// `trace_path` is where the phases of the default solver run are written in the Chrome format,
+// with `metrics` a summary of every `METRICS_INTERVAL`th step is printed
+fn bench(trace_path: Option<&str>, metrics: bool, compare_batched: bool) {
+    // the scalar link loop, against the SIMD batches when benchmarking
+    let modes: &[bool] = if compare_batched {
+        &[false, true]
+    } else {
+        &[false]
+    };
+    for &batched in modes {
+        let mut physics = Physics::new(&[demo_flag()]);
+        physics.set_bounds(WorldBounds::from_size(vec2(WIDTH as f32, HEIGHT as f32)));
+        physics.set_batched_links(batched);
+        if trace_path.is_some() && !batched {
+            physics.start_trace();
+        }
+        if metrics && !batched {
+            physics.set_metrics_sink(|m| {
+                if m.step_count % METRICS_INTERVAL == 0 {
+                    let phases = m
+                        .phases
+                        .iter()
+                        .map(|(name, d)| format!("{}={}us", name, d.as_micros()))
+                        .collect::<Vec<String>>();
+                    println!(
+                        "step={} time={:.3} max_speed={:.3} kinetic_energy={:.3} torn={} {}",
+                        m.step_count,
+                        m.time,
+                        m.max_speed,
+                        m.kinetic_energy,
+                        m.torn_links,
+                        phases.join(" ")
+                    );
+                }
+            });
+        }
+        let num_nodes = physics.get_points().len();
+        let dt = DT / physics.sub_steps() as f32;
+
+        let start = Instant::now();
+        for _ in 0..BENCH_STEPS {
+            physics.step(dt);
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        println!(
+            "{} links, nodes: {}, steps: {}",
+            if batched { "batched" } else { "scalar" },
+            num_nodes,
+            BENCH_STEPS
+        );
+        println!(
+            "nodes*steps per second: {:.0}, average step time: {:.2} us",
+            (num_nodes * BENCH_STEPS) as f64 / elapsed,
+            elapsed * 1e6 / BENCH_STEPS as f64
+        );
+
+        if let (Some(path), Some(trace)) = (trace_path, physics.take_trace()) {
+            match trace.write_chrome(path) {
+                Ok(()) => println!("trace written to {}", path),
+                Err(e) => eprintln!("could not write {}: {}", path, e),
+            }
+        }
+    }
+}
+
+// parses `R,G,B` or `R,G,B,A` with channels in 0..1, alpha defaults to opaque
+fn parse_color(value: &str) -> Result<(f32, f32, f32, f32), String> {
+    let channels = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|e| format!("invalid color `{}`: {}", value, e))?;
+    match channels[..] {
+        [r, g, b] => Ok((r, g, b, 1.0)),
+        [r, g, b, a] => Ok((r, g, b, a)),
+        _ => Err(format!("expected R,G,B or R,G,B,A, got `{}`", value)),
+    }
+}
+
+// parses `X,Y`
+fn parse_corner(value: &str) -> Result<Vec2, String> {
+    match value
+        .split_once(',')
+        .map(|(x, y)| (x.trim().parse(), y.trim().parse()))
+    {
+        Some((Ok(x), Ok(y))) => Ok(vec2(x, y)),
+        _ => Err(format!("expected X,Y, got `{}`", value)),
+    }
+}
+
+fn parse_line_width(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(width) if width >= 0.0 => Ok(width),
+        Ok(width) => Err(format!("expected a positive width, got {}", width)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn load_texture(path: &str) -> Result<image::RgbaImage, String> {
+    image::open(path)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| format!("could not open `{}`: {}", path, e))
+}
+
+fn load_settings(path: &str) -> Result<Settings, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read `{}`: {}", path, e))?;
+    ron::from_str(&text).map_err(|e| format!("invalid parameters in `{}`: {}", path, e))
+}
+
 fn main() {
+    let args = Args::parse();
+    // tracing runs the bench workload headless
+    if args.bench || args.metrics || args.trace_chrome.is_some() {
+        bench(args.trace_chrome.as_deref(), args.metrics, args.bench);
+        return;
+    }
+
+    if args.three_d {
+        let (width, height) = (args.width, args.height);
+        miniquad::start(
+            conf::Conf {
+                window_width: WIDTH as i32,
+                window_height: HEIGHT as i32,
+                high_dpi: true,
+                ..Default::default()
+            },
+            move |ctx| match view3d::Stage3d::new(ctx, width, height) {
+                Ok(stage) => Box::new(stage),
+                Err(e) => {
+                    eprintln!("could not set up the 3D view: {}", e);
+                    std::process::exit(1);
+                }
+            },
+        );
+        return;
+    }
+
+    let physics = match (&args.config, &args.mask) {
+        (Some(scene), _) => scene.build(),
+        (None, Some(path)) => mask_flag(path, args.corner, args.size),
+        (None, None) => demo_flags(&args).map(|flags| Physics::new(&flags)),
+    };
+    let physics = match physics {
+        Ok(physics) => physics,
+        Err(e) => {
+            eprintln!("could not set up the flags: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // an alpha of 0 leaves the window transparent where the framebuffer has an alpha channel
+    let clear_color = args.bg.unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let (settings, texture, line_width) = (args.params, args.texture, args.line_width);
+    let timings = args.timings;
     miniquad::start(
         conf::Conf {
             window_width: WIDTH as i32,
@@ -189,6 +1904,147 @@ fn main() {
             high_dpi: true,
             ..Default::default()
         },
-        |ctx| Box::new(Stage::new(ctx)),
+        move |ctx| {
+            let stage = Stage::new(
+                ctx,
+                physics,
+                clear_color,
+                settings,
+                texture,
+                line_width,
+                timings,
+            );
+            match stage {
+                Ok(stage) => Box::new(stage),
+                Err(e) => {
+                    eprintln!("could not set up the renderer: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_run_whole_fixed_steps_and_blend_the_remainder() {
+        let mut accumulator = 0.0;
+        // a frame and a half
+        accumulator += 1.5 * DT;
+        assert_eq!(fixed_steps(&mut accumulator, DT), 1);
+        assert!((interpolation_alpha(accumulator, DT) - 0.5).abs() < 1e-4);
+        // the half left over adds up with the next frame
+        accumulator += 1.75 * DT;
+        assert_eq!(fixed_steps(&mut accumulator, DT), 2);
+        assert!((interpolation_alpha(accumulator, DT) - 0.25).abs() < 1e-4);
+        // a frame shorter than a step only moves the blend
+        accumulator += 0.5 * DT;
+        assert_eq!(fixed_steps(&mut accumulator, DT), 0);
+        assert!((interpolation_alpha(accumulator, DT) - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interpolation_alpha_stays_between_the_two_states() {
+        assert_eq!(interpolation_alpha(0.0, DT), 0.0);
+        assert_eq!(interpolation_alpha(-DT, DT), 0.0);
+        assert_eq!(interpolation_alpha(2.0 * DT, DT), 1.0);
+    }
+
+    #[test]
+    fn stretched_links_are_colored_as_strained() {
+        let mut physics = Physics::new(&[FlagParams {
+            bend: true,
+            ..demo_flag()
+        }]);
+        let mut nodes = physics.nodes().to_vec();
+        let moved = nodes.len() / 2;
+        // far enough below the cloth to stretch every link it has, bend links included
+        nodes[moved].pos.y += 200.0;
+        physics.restore_nodes(&nodes);
+
+        let colors = strain_colors(&physics);
+        let indices = physics.get_indices();
+        assert_eq!(colors.len(), indices.len());
+        for (pair, colors) in indices.chunks(2).zip(colors.chunks(2)) {
+            let expected = if pair.contains(&(moved as u32)) {
+                // stretched well past the most strain shown
+                vec4(1.0, 0.0, 0.0, 1.0)
+            } else {
+                strain_color(0.0)
+            };
+            assert_eq!(colors, [expected; 2], "link {:?}", pair);
+        }
+    }
+
+    #[test]
+    fn dumped_settings_load_back_the_same() {
+        let flags = || [demo_flag(), demo_flag_at(Vec2::ZERO, 200.0, 5, 5).unwrap()];
+        let mut physics = Physics::new(&flags());
+        physics.set_sub_steps(7);
+        physics.set_clamp_mode(ClampMode::Velocity);
+        physics.set_link_solver(LinkSolver::Jacobi);
+        physics.set_constraint_enabled(ConstraintKind::Bend, false);
+        physics.set_interaction_solve(3, 25.0);
+        physics.set_startup_ramp_steps(40);
+        physics.set_substeps(1, 4);
+        physics.set_pressure(0, 120.0);
+        physics.set_drag(1, 0.3);
+        physics.set_gravity(1, vec2(10.0, -50.0));
+        physics.set_stiffness(0, 0.35);
+        physics.set_max_push(1, 2.5);
+        physics.set_damping(0, 0.02);
+
+        let text = ron::ser::to_string_pretty(&Settings::of(&physics), Default::default()).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("flags-{}-{}", std::process::id(), PARAMS_FILE));
+        fs::write(&path, text).unwrap();
+        let loaded = load_settings(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let mut reloaded = Physics::new(&flags());
+        loaded.unwrap().apply(&mut reloaded);
+        assert_eq!(reloaded.params(), physics.params());
+        assert_eq!(reloaded.sub_steps(), 7);
+    }
+
+    #[test]
+    fn triangles_face_the_other_way_across_a_fold() {
+        let mut physics = Physics::new(&[demo_flag_at(Vec2::ZERO, 400.0, 5, 3).unwrap()]);
+        // fold the right half of the flag over the left one, about its middle column
+        let fold = physics.get_points()[2].x;
+        let nodes = physics
+            .nodes()
+            .iter()
+            .map(|n| {
+                let mut n = *n;
+                n.pos.x = fold - (n.pos.x - fold).abs();
+                n
+            })
+            .collect::<Vec<_>>();
+        physics.restore_nodes(&nodes);
+
+        let proj = Mat4::orthographic_lh(0.0, 400.0, 400.0, 0.0, 0.0, 1.0);
+        let (vertices, _) = physics.get_exploded_triangles(0.0);
+        for (i, tri) in vertices.chunks(3).enumerate() {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|p| proj.project_point3(p.extend(0.0)));
+            // counter-clockwise in clip space is what GL sets `gl_FrontFacing` on
+            let front_facing = (b - a).truncate().perp_dot((c - a).truncate()) > 0.0;
+            let folded = (i / 2) % 4 >= 2;
+            assert_eq!(front_facing, folded, "triangle {}", i);
+        }
+    }
+
+    #[test]
+    fn screen_points_map_to_the_world_under_them() {
+        // zoomed in twice on a high dpi screen, with twice the pixels of the logical view
+        let proj = world_projection(vec2(100.0, 50.0), vec2(800.0, 600.0), 2.0);
+        let screen = vec2(1600.0, 1200.0);
+        let world = |x, y| screen_to_world(proj, screen, vec2(x, y));
+        assert!(world(0.0, 0.0).distance(vec2(100.0, 50.0)) < 1e-3);
+        assert!(world(400.0, 300.0).distance(vec2(200.0, 125.0)) < 1e-3);
+        assert!(world(1600.0, 1200.0).distance(vec2(500.0, 350.0)) < 1e-3);
+    }
+}
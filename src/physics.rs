@@ -1,36 +1,215 @@
-use glam::{vec2, Vec2};
+use std::{
+    fs, io,
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use crate::{chunk_iter::ChunksMutIndices, HEIGHT, WIDTH};
+use glam::{vec2, Vec2, Vec4};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{chunk_iter::ChunksMutIndices, spatial_hash::SpatialHash, trace::Trace};
 use rayon::prelude::*;
 
-#[derive(Clone, Copy, Default, Debug)]
+// steps `advance` splits a frame into, more make a stiffer and steadier cloth
+const DEFAULT_SUB_STEPS: usize = 10;
+// length of the silent steps settling new flags, a sub-step of a 60 fps frame
+const SETTLE_DT: f32 = 1.0 / 60.0 / DEFAULT_SUB_STEPS as f32;
+// with adaptive sub-steps, the fastest node should travel at most this fraction of the shortest
+// link in a sub-step
+const ADAPTIVE_MAX_TRAVEL: f32 = 0.05;
+// pixels of an image mask at least this opaque become nodes
+const MASK_MIN_ALPHA: u8 = 128;
+// opaque pixels of this color in an image mask become pinned nodes
+const MASK_PIN_COLOR: [u8; 3] = [255, 0, 0];
+// stiffness of flags loaded from a file
+const DEFAULT_STIFFNESS: f32 = 0.5;
+// cap on the correction of compressed links of flags loaded from a file
+const DEFAULT_MAX_PUSH: f32 = 0.001;
+// stiffness of each constraint kind relative to the structural links, the shear and bend links
+// only resist large deformations so the cloth doesn't turn into a rigid sheet
+const KIND_STIFFNESS: [f32; 3] = [1.0, 0.5, 0.25];
+// distance kept between nodes by the self-collision, relative to the shortest structural link
+const COLLISION_RADIUS: f32 = 0.5;
+// links solved together by the batched solver
+const LANES: usize = 4;
+// over-relaxation of the averaged Jacobi corrections, which alone converge much slower than
+// Gauss-Seidel, below 2.0 to stay stable
+const JACOBI_RELAXATION: f32 = 1.5;
+// the phases of a flag's substep, in the order `Flag::step` runs them
+const FLAG_PHASES: [&str; 9] = [
+    "update_pos",
+    "wind",
+    "pressure",
+    "drag",
+    "constraint",
+    "links",
+    "obstacles",
+    "self_collision",
+    "tear",
+];
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Node {
     pub pos: Vec2,
     pub last_pos: Vec2,
     pinned: bool,
+    // a gap in the cloth, such as a transparent pixel of an image mask, which never moves and
+    // which nothing but the grid indexing sees
+    #[cfg_attr(feature = "serde", serde(default))]
+    absent: bool,
+    // overrides the damping of its flag for this node
+    damping: Option<f32>,
+    // one over the mass, the share of a correction a node takes grows with it
+    inv_mass: f32,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node {
+            pos: Vec2::ZERO,
+            last_pos: Vec2::ZERO,
+            pinned: false,
+            absent: false,
+            damping: None,
+            inv_mass: 1.0,
+        }
+    }
+}
+
+impl Node {
+    // pinned and absent nodes weigh as if infinitely heavy
+    fn weight(&self) -> f32 {
+        if self.pinned || self.absent {
+            0.0
+        } else {
+            self.inv_mass
+        }
+    }
+
+    // whether forces and walls move the node
+    fn free(&self) -> bool {
+        !self.pinned && !self.absent
+    }
+}
+
+// the fractions of a correction between two nodes taken by each of them, None if neither moves
+fn correction_shares(a: &Node, b: &Node) -> Option<(f32, f32)> {
+    let (wa, wb) = (a.weight(), b.weight());
+    let total = wa + wb;
+    (total > 0.0).then(|| (wa / total, wb / total))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConstraintKind {
+    Structural,
+    Shear,
+    Bend,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Link {
     node1: usize,
     node2: usize,
     resting_distance: f32,
+    kind: ConstraintKind,
+    // torn links are kept so their indices stay stable, but are no longer solved or drawn
+    dead: bool,
 }
 
 impl Link {
-    fn new(nodes: &[Node], node1: usize, node2: usize) -> Self {
+    fn new(nodes: &[Node], node1: usize, node2: usize, kind: ConstraintKind) -> Self {
         Self {
             node1,
             node2,
             resting_distance: nodes[node1].pos.distance(nodes[node2].pos),
+            kind,
+            dead: false,
         }
     }
 }
 
+// `links` with the indices shifted by `offset`, from the flag's nodes to all the nodes
+fn offset_links(links: &[Link], offset: usize) -> Vec<Link> {
+    links
+        .iter()
+        .map(|l| Link {
+            node1: l.node1 + offset,
+            node2: l.node2 + offset,
+            ..*l
+        })
+        .collect()
+}
+
+// whether the segments `a`-`b` and `c`-`d` cross, each one having the ends of the other on
+// opposite sides, so segments that only touch or lie on the same line do not count
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    side(a, b, c) * side(a, b, d) < 0.0 && side(c, d, a) * side(c, d, b) < 0.0
+}
+
+// json has no infinity, so the tear factor of a flag which never tears is written as null
+#[cfg(feature = "serde")]
+mod finite_or_null {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        value.is_finite().then_some(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::INFINITY))
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Flag {
+    offset: usize,
     width: usize,
     height: usize,
+    substeps: usize,
+    pressure: f32,
+    // air resistance of the outline, 0 to ignore the air
+    #[cfg_attr(feature = "serde", serde(default))]
+    drag: f32,
+    camber: f32,
+    gravity: Vec2,
+    stiffness: f32,
+    max_push: f32,
+    damping: f32,
+    // links stretched beyond this multiple of their rest length tear
+    #[cfg_attr(feature = "serde", serde(with = "finite_or_null"))]
+    tear_factor: f32,
+    // links were torn or mended since `Physics::take_links_changed`, so the indices are stale
+    #[cfg_attr(feature = "serde", serde(alias = "torn"))]
+    links_changed: bool,
     links: Vec<Link>,
+    // links reaching outside an image mask, dead from the start and through every reset
+    #[cfg_attr(feature = "serde", serde(default))]
+    holes: Vec<usize>,
+    // derived from `links`, not saved
+    #[cfg_attr(feature = "serde", serde(skip))]
     offset_links: Vec<Link>,
+    // lengths measured by the last `apply_links`, empty unless the cache is enabled
+    link_lengths: Vec<f32>,
+    // link indices split in groups sharing no node, empty unless batched solving is enabled
+    link_colors: Vec<Vec<usize>>,
+    // nodes binned by position, only present while the self-collision is enabled
+    #[cfg_attr(feature = "serde", serde(skip))]
+    collision: Option<SpatialHash>,
+    // time spent in each of FLAG_PHASES since the last `take_phase_times`, only while timing
+    #[cfg_attr(feature = "serde", serde(skip))]
+    phase_times: Option<[Duration; FLAG_PHASES.len()]>,
+    // scratch of the Jacobi solver: the correction of both ends of each active link and its
+    // length, then the sum of the corrections of each node and their count
+    #[cfg_attr(feature = "serde", serde(skip))]
+    link_corrections: Vec<Option<(Vec2, Vec2, f32)>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    node_corrections: Vec<(Vec2, u32)>,
 }
 
 impl Flag {
@@ -47,206 +226,3583 @@ impl Flag {
                 nodes[x + y * width].pos =
                     vec2(x as f32, y as f32) * (size / width as f32) + corner;
                 nodes[x + y * width].last_pos = nodes[x + y * width].pos;
-                nodes[x + y * width].pinned = x == 0 && (y == 0 || y == height - 1);
             }
         }
 
+        Self::from_nodes(nodes, node_offset, width, height)
+    }
+
+    // builds the grid links from the positions already set in `nodes`, which become the rest shape
+    fn from_nodes(nodes: &mut [Node], node_offset: usize, width: usize, height: usize) -> Self {
+        let structural = ConstraintKind::Structural;
         let links = (0..height)
             .flat_map(|y| {
                 let n = &nodes;
-                (0..(width - 1)).map(move |x| Link::new(n, x + y * width, x + 1 + y * width))
+                (0..(width - 1))
+                    .map(move |x| Link::new(n, x + y * width, x + 1 + y * width, structural))
             })
             .chain((0..width).flat_map(|x| {
                 let n = &nodes;
-                (0..(height - 1)).map(move |y| Link::new(n, x + y * width, x + (y + 1) * width))
+                (0..(height - 1))
+                    .map(move |y| Link::new(n, x + y * width, x + (y + 1) * width, structural))
             }))
             .collect::<Vec<Link>>();
 
-        Self {
+        let flag = Self {
+            offset: node_offset,
             width,
             height,
-            offset_links: links
-                .iter()
-                .map(|l| Link {
-                    node1: l.node1 + node_offset,
-                    node2: l.node2 + node_offset,
-                    resting_distance: l.resting_distance,
-                })
-                .collect(),
+            substeps: 1,
+            pressure: 0.0,
+            drag: 0.0,
+            camber: 0.0,
+            gravity: Vec2::ZERO,
+            stiffness: DEFAULT_STIFFNESS,
+            max_push: DEFAULT_MAX_PUSH,
+            damping: 0.0,
+            tear_factor: f32::INFINITY,
+            links_changed: false,
+            offset_links: offset_links(&links, node_offset),
             links,
+            holes: vec![],
+            link_lengths: vec![],
+            link_colors: vec![],
+            collision: None,
+            phase_times: None,
+            link_corrections: vec![],
+            node_corrections: vec![],
+        };
+        flag.pin(nodes, &Pinning::default());
+        flag
+    }
+
+    // pins the nodes picked by `pinning` and frees the others
+    fn pin(&self, nodes: &mut [Node], pinning: &Pinning) {
+        let (width, height) = (self.width, self.height);
+        for (i, n) in nodes.iter_mut().enumerate() {
+            let (x, y) = (i % width, i / width);
+            n.pinned = match pinning {
+                Pinning::TopLeftCorners => x == 0 && (y == 0 || y == height - 1),
+                Pinning::TopEdge => y == 0,
+                Pinning::FourCorners => (x == 0 || x == width - 1) && (y == 0 || y == height - 1),
+                Pinning::LeftEdge => x == 0,
+                Pinning::Custom(pinned) => pinned.contains(&i),
+            };
         }
     }
-}
 
-pub struct FlagParams {
-    pub size: f32,
-    pub corner: Vec2,
-    pub width: usize,
-    pub height: usize,
-}
+    // the dead links which aren't holes
+    fn num_torn(&self) -> usize {
+        self.links.iter().filter(|l| l.dead).count() - self.holes.len()
+    }
 
-pub struct Physics {
-    nodes: Vec<Node>,
-    flags: Vec<Flag>,
+    // revives the torn links, but not the holes
+    fn mend(&mut self) {
+        for l in self.links.iter_mut().chain(&mut self.offset_links) {
+            l.dead = false;
+        }
+        for &i in &self.holes {
+            self.links[i].dead = true;
+            self.offset_links[i].dead = true;
+        }
+        self.links_changed = true;
+    }
 
-    selected_nodes: Option<Vec<usize>>,
-}
+    // adds links between pairs of nodes local to the flag, after the existing ones
+    fn add_links(
+        &mut self,
+        nodes: &[Node],
+        kind: ConstraintKind,
+        pairs: impl Iterator<Item = (usize, usize)>,
+    ) {
+        for (n1, n2) in pairs {
+            let link = Link::new(nodes, n1, n2, kind);
+            self.offset_links.push(Link {
+                node1: n1 + self.offset,
+                node2: n2 + self.offset,
+                ..link
+            });
+            self.links.push(link);
+        }
+    }
 
-impl Physics {
-    pub fn new(flag_sizes: &[FlagParams]) -> Physics {
-        let mut nodes =
-            vec![Node::default(); flag_sizes.iter().map(|fp| fp.width * fp.height).sum()];
+    // the two diagonals of every cell
+    fn add_shear_links(&mut self, nodes: &[Node]) {
+        let (width, height) = (self.width, self.height);
+        let pairs = (0..height - 1)
+            .flat_map(|y| (0..width - 1).map(move |x| x + y * width))
+            .flat_map(|i| [(i, i + width + 1), (i + 1, i + width)])
+            .collect::<Vec<_>>();
+        self.add_links(nodes, ConstraintKind::Shear, pairs.into_iter());
+    }
 
-        let offsets = flag_sizes
-            .iter()
-            .scan(0, |acc, fp| {
-                let offset = *acc;
-                *acc += fp.width * fp.height;
-                Some(offset)
-            })
-            .collect::<Vec<usize>>();
+    // links skipping one node, along the rows and the columns
+    fn add_bend_links(&mut self, nodes: &[Node]) {
+        let (width, height) = (self.width, self.height);
+        let horizontal = (0..height).flat_map(|y| {
+            (0..width.saturating_sub(2)).map(move |x| (x + y * width, x + 2 + y * width))
+        });
+        let vertical = (0..height.saturating_sub(2))
+            .flat_map(|y| (0..width).map(move |x| (x + y * width, x + (y + 2) * width)));
+        let pairs = horizontal.chain(vertical).collect::<Vec<_>>();
+        self.add_links(nodes, ConstraintKind::Bend, pairs.into_iter());
+    }
 
-        let flags = flag_sizes
-            .iter()
-            .zip(offsets)
-            .map(|(fp, offset)| {
-                Flag::new(
-                    &mut nodes[offset..(offset + fp.width * fp.height)],
-                    offset,
-                    fp.corner,
-                    fp.size,
-                    fp.width,
-                    fp.height,
-                )
-            })
-            .collect();
+    // global index of the node `i` steps along `edge`, from left to right or top to bottom
+    fn edge_node(&self, edge: Edge, i: usize) -> usize {
+        let (x, y) = match edge {
+            Edge::Top => (i, 0),
+            Edge::Bottom => (i, self.height - 1),
+            Edge::Left => (0, i),
+            Edge::Right => (self.width - 1, i),
+        };
+        self.offset + x + y * self.width
+    }
 
-        //    vec![Flag::new(&mut nodes, 0, vec2(100.0, 100.0), 100.0, 10, 10)];
-        Physics {
-            nodes,
-            flags,
-            selected_nodes: None,
+    fn edge_len(&self, edge: Edge) -> usize {
+        match edge {
+            Edge::Top | Edge::Bottom => self.width,
+            Edge::Left | Edge::Right => self.height,
         }
     }
 
-    fn update_pos(&mut self, gravity: Vec2, dt: f32) {
-        self.nodes.iter_mut().filter(|n| !n.pinned).for_each(|n| {
-            let diff = n.pos - n.last_pos;
-            n.last_pos = n.pos;
-            n.pos += (diff + gravity * (dt * dt)).clamp_length_max(50.0);
-        });
+    // scale applied by the camber to the rest length of the horizontal links of row `y`
+    fn camber_scale(&self, camber: f32, y: usize) -> f32 {
+        let t = if self.height > 1 {
+            y as f32 / (self.height - 1) as f32
+        } else {
+            0.5
+        };
+        1.0 + camber * (t - 0.5)
     }
 
-    fn apply_constraint(&mut self) {
-        let factor = 0.75;
-        self.nodes.iter_mut().filter(|n| !n.pinned).for_each(|n| {
-            if n.pos.x > WIDTH as f32 {
-                n.pos.x += factor * (WIDTH as f32 - n.pos.x);
-            }
-            if n.pos.x < 0.0 {
-                n.pos.x -= factor * n.pos.x;
+    fn alive_links(&self) -> impl Iterator<Item = &Link> {
+        self.offset_links.iter().filter(|l| !l.dead)
+    }
+
+    // a cell is torn once any of its four structural links is dead, the horizontal links are
+    // stored first row by row, followed by the vertical ones column by column
+    fn cell_torn(&self, x: usize, y: usize) -> bool {
+        let horizontal = |x: usize, y: usize| y * (self.width - 1) + x;
+        let vertical =
+            |x: usize, y: usize| self.height * (self.width - 1) + x * (self.height - 1) + y;
+        [
+            horizontal(x, y),
+            horizontal(x, y + 1),
+            vertical(x, y),
+            vertical(x + 1, y),
+        ]
+        .iter()
+        .any(|&i| self.links[i].dead)
+    }
+
+    // node indices of each intact grid cell, local to the flag
+    fn cells(&self) -> impl Iterator<Item = [usize; 4]> + '_ {
+        let width = self.width;
+        (0..(self.height - 1))
+            .flat_map(move |y| (0..(width - 1)).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.cell_torn(x, y))
+            .map(move |(x, y)| {
+                let i = x + y * width;
+                [i, i + 1, i + 1 + width, i + width]
+            })
+    }
+
+    // advances the flag by `settings.dt`, split into its own number of substeps
+    fn step(&mut self, nodes: &mut [Node], settings: &StepSettings) {
+        let dt = settings.dt / self.substeps as f32;
+        let mut times = self.phase_times.take();
+        let mut lap = Instant::now();
+        let mut end_phase = |phase: usize| {
+            if let Some(times) = &mut times {
+                let now = Instant::now();
+                times[phase] += now - lap;
+                lap = now;
             }
-            if n.pos.y > HEIGHT as f32 {
-                n.pos.y += factor * (HEIGHT as f32 - n.pos.y);
+        };
+        for _ in 0..self.substeps {
+            update_pos(nodes, settings, self.gravity, self.damping, dt);
+            end_phase(0);
+            apply_wind(nodes, settings.wind_fields, dt);
+            end_phase(1);
+            self.apply_pressure(nodes, dt);
+            end_phase(2);
+            self.apply_drag(nodes, settings, dt);
+            end_phase(3);
+            apply_constraint(nodes, settings.bounds);
+            end_phase(4);
+            self.apply_links(nodes, settings);
+            end_phase(5);
+            apply_obstacles(nodes, settings.obstacles);
+            end_phase(6);
+            self.apply_self_collision(nodes);
+            end_phase(7);
+            self.tear_links(nodes);
+            end_phase(8);
+        }
+        self.phase_times = times;
+    }
+
+    fn set_mass(&self, nodes: &mut [Node], [top, bottom]: [f32; 2]) {
+        for y in 0..self.height {
+            let t = y as f32 / (self.height - 1).max(1) as f32;
+            let inv_mass = 1.0 / (top + (bottom - top) * t);
+            for n in &mut nodes[y * self.width..(y + 1) * self.width] {
+                n.inv_mass = inv_mass;
             }
-            if n.pos.y < 0.0 {
-                n.pos.y -= factor * n.pos.y;
+        }
+    }
+
+    fn collision_radius(&self) -> f32 {
+        let shortest = self
+            .links
+            .iter()
+            .filter(|l| l.kind == ConstraintKind::Structural)
+            .map(|l| l.resting_distance)
+            .fold(f32::INFINITY, f32::min);
+        shortest * COLLISION_RADIUS
+    }
+
+    // pushes apart the nodes closer than the collision radius, sharing the overlap by weight
+    fn apply_self_collision(&mut self, nodes: &mut [Node]) {
+        let Some(hash) = &mut self.collision else {
+            return;
+        };
+        let radius = hash.cell_size();
+        hash.rebuild(nodes.iter().map(|n| n.pos));
+        for i in 0..nodes.len() {
+            if nodes[i].absent {
+                continue;
             }
-        });
+            hash.for_each_near(nodes[i].pos, |j| {
+                if j <= i || nodes[j].absent {
+                    return;
+                }
+                let diff = nodes[i].pos - nodes[j].pos;
+                let dist2 = diff.length_squared();
+                if dist2 >= radius * radius || dist2 == 0.0 {
+                    return;
+                }
+                let dist = dist2.sqrt();
+                let push = diff * ((radius - dist) / dist);
+                if let Some((si, sj)) = correction_shares(&nodes[i], &nodes[j]) {
+                    nodes[i].pos += push * si;
+                    nodes[j].pos -= push * sj;
+                }
+            });
+        }
     }
 
-    fn apply_links(&mut self) {
-        let breakpoints = self
-            .flags
+    // marks the overstretched links dead in a second pass, once they are all solved
+    fn tear_links(&mut self, nodes: &[Node]) {
+        if self.tear_factor == f32::INFINITY {
+            return;
+        }
+        let torn = self
+            .links
             .iter()
-            .map(|f| f.width * f.height)
-            .scan(0, |acc, x| {
-                let offset = *acc;
-                *acc += x;
-                Some(offset)
+            .enumerate()
+            .filter(|(_, l)| !l.dead)
+            .filter(|(_, l)| {
+                let dist = nodes[l.node1].pos.distance(nodes[l.node2].pos);
+                dist > l.resting_distance * self.tear_factor
             })
+            .map(|(i, _)| i)
             .collect::<Vec<usize>>();
-        let chunks: ChunksMutIndices<'_, Node> =
-            ChunksMutIndices::new(&mut self.nodes, &breakpoints);
+        for i in torn {
+            self.links[i].dead = true;
+            self.offset_links[i].dead = true;
+            self.links_changed = true;
+        }
+    }
 
-        self.flags
-            .iter()
-            .zip(chunks)
-            .par_bridge()
-            .for_each(|(flag, (nodes, _))| {
-                flag.links.iter().for_each(|link| {
-                    let diff = nodes[link.node1].pos - nodes[link.node2].pos;
-                    let dist = diff.length();
-                    let force = ((link.resting_distance - dist) / dist * 0.5).min(0.001);
-                    let n = diff * force;
-                    if !nodes[link.node1].pinned {
-                        nodes[link.node1].pos += n;
-                    }
-                    if !nodes[link.node2].pinned {
-                        nodes[link.node2].pos -= n;
-                    }
+    fn apply_pressure(&self, nodes: &mut [Node], dt: f32) {
+        if self.pressure == 0.0 {
+            return;
+        }
+        let mut forces = vec![];
+        for quad in self.cells() {
+            let corners = quad.map(|i| nodes[i].pos);
+            let center = corners.iter().sum::<Vec2>() / 4.0;
+            for k in 0..4 {
+                let (a, b) = (corners[k], corners[(k + 1) % 4]);
+                // the edge normal scaled by its length, pointing away from the cell
+                let mut normal = (b - a).perp();
+                if normal.dot((a + b) * 0.5 - center) < 0.0 {
+                    normal = -normal;
+                }
+                let force = normal * (0.5 * self.pressure);
+                forces.push((quad[k], force));
+                forces.push((quad[(k + 1) % 4], force));
+            }
+        }
+
+        for (i, force) in forces {
+            let n = &mut nodes[i];
+            if n.free() {
+                n.pos += force * (n.inv_mass * dt * dt);
+            }
+        }
+    }
+
+    // Air resistance on the outline of the cloth, holes and tears included. Each outline edge is
+    // pushed along its normal by the normal component of the air's velocity relative to it,
+    // times its length, the air moving at the wind. An outline spread across the wind catches
+    // it while one lying along it lets it slide by, and without wind the air slows the cloth.
+    fn apply_drag(&self, nodes: &mut [Node], settings: &StepSettings, dt: f32) {
+        if self.drag == 0.0 {
+            return;
+        }
+        let (cols, rows) = (self.width - 1, self.height - 1);
+        let intact = (0..rows)
+            .flat_map(|y| (0..cols).map(move |x| !self.cell_torn(x, y)))
+            .collect::<Vec<bool>>();
+        // whether the cell at `x + dx`, `y + dy` is part of the cloth
+        let cloth = |x: usize, y: usize, dx: isize, dy: isize| {
+            let (x, y) = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+            x < cols && y < rows && intact[x + y * cols]
+        };
+
+        let mut forces = vec![];
+        for (y, x) in (0..rows).flat_map(|y| (0..cols).map(move |x| (y, x))) {
+            if !intact[x + y * cols] {
+                continue;
+            }
+            let i = x + y * self.width;
+            let quad = [i, i + 1, i + 1 + self.width, i + self.width];
+            let center = quad.iter().map(|&i| nodes[i].pos).sum::<Vec2>() / 4.0;
+            // top, right, bottom and left, with the cell across each of them
+            for (k, (dx, dy)) in [(0, -1), (1, 0), (0, 1), (-1, 0)].into_iter().enumerate() {
+                if cloth(x, y, dx, dy) {
+                    continue;
+                }
+                let (a, b) = (&nodes[quad[k]], &nodes[quad[(k + 1) % 4]]);
+                let mid = (a.pos + b.pos) * 0.5;
+                // the edge normal scaled by its length, pointing away from the cell
+                let mut normal = (b.pos - a.pos).perp();
+                if normal.dot(mid - center) < 0.0 {
+                    normal = -normal;
+                }
+                let air = match &settings.gust {
+                    Some(gust) => settings.wind * gust.scale(settings.wind, mid, settings.time),
+                    None => settings.wind,
+                };
+                let velocity = (a.pos - a.last_pos + b.pos - b.last_pos) / (2.0 * dt);
+                let force = normal * ((air - velocity).dot(normal.normalize_or_zero()) * self.drag);
+                forces.push((quad[k], force * 0.5));
+                forces.push((quad[(k + 1) % 4], force * 0.5));
+            }
+        }
+
+        for (i, force) in forces {
+            let n = &mut nodes[i];
+            if n.free() {
+                n.pos += force * (n.inv_mass * dt * dt);
+            }
+        }
+    }
+
+    fn apply_links(&mut self, nodes: &mut [Node], settings: &StepSettings) {
+        let enabled = settings.constraint_enabled;
+        let stiffness = self.stiffness * settings.stiffness_scale;
+        if settings.link_solver == LinkSolver::Jacobi {
+            self.apply_links_jacobi(nodes, enabled, stiffness);
+        } else if !self.link_colors.is_empty() {
+            self.apply_links_batched(nodes, enabled, stiffness);
+        } else if self.link_lengths.is_empty() {
+            self.links
+                .iter()
+                .filter(|link| !link.dead && enabled[link.kind as usize])
+                .for_each(|link| {
+                    solve_link(nodes, link, stiffness, self.max_push);
                 })
-            })
+        } else {
+            self.links
+                .iter()
+                .zip(self.link_lengths.iter_mut())
+                .filter(|(link, _)| !link.dead && enabled[link.kind as usize])
+                .for_each(|(link, len)| *len = solve_link(nodes, link, stiffness, self.max_push))
+        }
+    }
+
+    // Same constraint as `apply_links`, solved `LANES` links at a time. Links are visited color
+    // by color rather than in storage order, so results differ slightly from the scalar loop.
+    fn apply_links_batched(&mut self, nodes: &mut [Node], enabled: [bool; 3], stiffness: f32) {
+        let (links, max_push) = (&self.links, self.max_push);
+        let active = |&i: &usize| !links[i].dead && enabled[links[i].kind as usize];
+        for color in &self.link_colors {
+            let mut batch = [0; LANES];
+            let mut len = 0;
+            for i in color.iter().copied().filter(active) {
+                batch[len] = i;
+                len += 1;
+                if len == LANES {
+                    let lengths = &mut self.link_lengths;
+                    solve_link_batch(nodes, links, &batch, stiffness, max_push, lengths);
+                    len = 0;
+                }
+            }
+            solve_link_batch(
+                nodes,
+                links,
+                &batch[..len],
+                stiffness,
+                max_push,
+                &mut self.link_lengths,
+            );
+        }
+    }
+
+    // Same constraint as `apply_links`, with every link solved from the positions at the start
+    // of the pass and the corrections of each node averaged before being applied. The links no
+    // longer depend on the ones solved before them, so they are solved in parallel and their
+    // order doesn't matter, but a correction only reaches the neighbouring links on the next
+    // pass: for the same substeps the cloth comes out stretchier than with Gauss-Seidel.
+    fn apply_links_jacobi(&mut self, nodes: &mut [Node], enabled: [bool; 3], stiffness: f32) {
+        let (links, max_push) = (&self.links, self.max_push);
+        let positions: &[Node] = nodes;
+        self.link_corrections.resize(links.len(), None);
+        self.link_corrections
+            .par_iter_mut()
+            .zip(links)
+            .for_each(|(correction, link)| {
+                *correction = (!link.dead && enabled[link.kind as usize])
+                    .then(|| link_correction(positions, link, stiffness, max_push));
+            });
+
+        self.node_corrections.clear();
+        self.node_corrections.resize(nodes.len(), (Vec2::ZERO, 0));
+        for (link, correction) in links.iter().zip(&self.link_corrections) {
+            if let Some((d1, d2, _)) = correction {
+                let sum = &mut self.node_corrections;
+                sum[link.node1] = (sum[link.node1].0 + *d1, sum[link.node1].1 + 1);
+                sum[link.node2] = (sum[link.node2].0 + *d2, sum[link.node2].1 + 1);
+            }
+        }
+        for (n, &(sum, count)) in nodes.iter_mut().zip(&self.node_corrections) {
+            if count > 0 {
+                n.pos += sum * (JACOBI_RELAXATION / count as f32);
+            }
+        }
+
+        if !self.link_lengths.is_empty() {
+            for (len, correction) in self.link_lengths.iter_mut().zip(&self.link_corrections) {
+                if let Some((_, _, dist)) = correction {
+                    *len = *dist;
+                }
+            }
+        }
+    }
+}
+
+// greedy coloring of the links so that two links of the same color never share a node
+fn color_links(links: &[Link], num_nodes: usize) -> Vec<Vec<usize>> {
+    let mut colors: Vec<Vec<usize>> = vec![];
+    // colors already touching each node
+    let mut used = vec![vec![]; num_nodes];
+    for (i, link) in links.iter().enumerate() {
+        let color = (0..)
+            .find(|c| !used[link.node1].contains(c) && !used[link.node2].contains(c))
+            .unwrap();
+        if color == colors.len() {
+            colors.push(vec![]);
+        }
+        colors[color].push(i);
+        used[link.node1].push(color);
+        used[link.node2].push(color);
     }
+    colors
+}
+
+fn update_pos(nodes: &mut [Node], settings: &StepSettings, gravity: Vec2, damping: f32, dt: f32) {
+    let wind = settings.wind;
+    nodes.iter_mut().filter(|n| n.free()).for_each(|n| {
+        let diff = (n.pos - n.last_pos) * (1.0 - n.damping.unwrap_or(damping));
+        let force = match &settings.gust {
+            Some(gust) => gravity + wind * gust.scale(wind, n.pos, settings.time),
+            None => gravity + wind,
+        };
+        n.last_pos = n.pos;
+        n.pos += match settings.clamp_mode {
+            ClampMode::Total => (diff + force * (dt * dt)).clamp_length_max(50.0),
+            ClampMode::Velocity => diff.clamp_length_max(50.0) + force * (dt * dt),
+        };
+    });
+}
 
-    pub fn step(&mut self, gravity: Vec2, dt: f32) {
-        self.update_pos(gravity, dt);
-        self.apply_constraint();
-        self.apply_links();
+fn apply_wind(nodes: &mut [Node], fields: &[(WindId, WindField)], dt: f32) {
+    if fields.is_empty() {
+        return;
     }
+    nodes.iter_mut().filter(|n| n.free()).for_each(|n| {
+        let force = fields.iter().map(|(_, f)| f.force_at(n.pos)).sum::<Vec2>();
+        n.pos += force * (dt * dt);
+    });
+}
 
-    pub fn _avoid_obstacle(&mut self, pos: Vec2, size: f32) {
-        self.nodes.iter_mut().filter(|n| !n.pinned).for_each(|p| {
-            let v = p.pos - pos;
+// projects the nodes inside an obstacle back onto its surface
+fn apply_obstacles(nodes: &mut [Node], obstacles: &[(ObstacleId, Obstacle)]) {
+    if obstacles.is_empty() {
+        return;
+    }
+    nodes.par_iter_mut().filter(|n| n.free()).for_each(|n| {
+        for (_, o) in obstacles {
+            let v = n.pos - o.center;
             let dist2 = v.length_squared();
-            let min_dist = size;
-            if dist2 < min_dist * min_dist {
-                let dist = dist2.sqrt();
-                let n = v / dist;
-                p.pos -= n * 0.1 * (dist - min_dist);
+            if dist2 < o.radius * o.radius && dist2 > 0.0 {
+                n.pos = o.center + v * (o.radius / dist2.sqrt());
             }
-        })
+        }
+    });
+}
+
+// Hard walls: a node past `bounds` is put back onto them every substep. Only the position across
+// the wall is clamped, so the cloth keeps sliding along it instead of sticking.
+fn apply_constraint(nodes: &mut [Node], bounds: WorldBounds) {
+    nodes.iter_mut().filter(|n| n.free()).for_each(|n| {
+        // max then min rather than `clamp`, which panics on bounds inset past each other
+        n.pos = n.pos.max(bounds.min).min(bounds.max);
+    });
+}
+
+fn solve_link(nodes: &mut [Node], link: &Link, stiffness: f32, max_push: f32) -> f32 {
+    let (d1, d2, dist) = link_correction(nodes, link, stiffness, max_push);
+    nodes[link.node1].pos += d1;
+    nodes[link.node2].pos += d2;
+    dist
+}
+
+// how far each end of `link` moves to restore its rest length, and its current length
+fn link_correction(
+    nodes: &[Node],
+    link: &Link,
+    stiffness: f32,
+    max_push: f32,
+) -> (Vec2, Vec2, f32) {
+    let diff = nodes[link.node1].pos - nodes[link.node2].pos;
+    let dist = diff.length();
+    let stiffness = stiffness * KIND_STIFFNESS[link.kind as usize];
+    let force = ((link.resting_distance - dist) / dist * stiffness).min(max_push);
+    // `force` is the share of each end when both weigh the same
+    let n = diff * (force * 2.0);
+    match correction_shares(&nodes[link.node1], &nodes[link.node2]) {
+        Some((s1, s2)) => (n * s1, -(n * s2), dist),
+        None => (Vec2::ZERO, Vec2::ZERO, dist),
     }
+}
 
-    pub fn get_indices(&self) -> Vec<i16> {
-        self.flags
-            .iter()
-            .flat_map(|f| f.offset_links.iter())
-            .flat_map(|l| [l.node1 as i16, l.node2 as i16])
-            .collect()
+// `solve_link` for up to `LANES` links sharing no node, the lengths are stored in `lengths`
+// when it is not empty
+fn solve_link_batch(
+    nodes: &mut [Node],
+    links: &[Link],
+    batch: &[usize],
+    stiffness: f32,
+    max_push: f32,
+    lengths: &mut [f32],
+) {
+    if batch.is_empty() {
+        return;
+    }
+    // unused lanes are filled with a link at rest so they compute a zero correction
+    let mut p1 = [[1.0; LANES]; 2];
+    let mut p2 = [[0.0; LANES]; 2];
+    let mut rest = [1.0; LANES];
+    let mut lane_stiffness = [0.0; LANES];
+    for (lane, &i) in batch.iter().enumerate() {
+        let link = &links[i];
+        let (a, b) = (nodes[link.node1].pos, nodes[link.node2].pos);
+        (p1[0][lane], p1[1][lane]) = (a.x, a.y);
+        (p2[0][lane], p2[1][lane]) = (b.x, b.y);
+        rest[lane] = link.resting_distance;
+        lane_stiffness[lane] = stiffness * KIND_STIFFNESS[link.kind as usize];
     }
 
-    pub fn get_points(&self) -> Vec<Vec2> {
-        self.nodes.iter().map(|n| n.pos).collect()
+    let dx = Vec4::from(p1[0]) - Vec4::from(p2[0]);
+    let dy = Vec4::from(p1[1]) - Vec4::from(p2[1]);
+    let dist = Vec4::from((dx * dx + dy * dy).to_array().map(f32::sqrt));
+    let force =
+        ((Vec4::from(rest) - dist) / dist * Vec4::from(lane_stiffness)).min(Vec4::splat(max_push));
+    let (nx, ny, dist) = (
+        (dx * force).to_array(),
+        (dy * force).to_array(),
+        dist.to_array(),
+    );
+
+    for (lane, &i) in batch.iter().enumerate() {
+        let link = &links[i];
+        let n = vec2(nx[lane], ny[lane]) * 2.0;
+        if let Some((s1, s2)) = correction_shares(&nodes[link.node1], &nodes[link.node2]) {
+            nodes[link.node1].pos += n * s1;
+            nodes[link.node2].pos -= n * s2;
+        }
+        if !lengths.is_empty() {
+            lengths[i] = dist[lane];
+        }
     }
+}
 
-    pub fn num_links(&self) -> i32 {
-        self.flags.iter().map(|f| f.links.len() as i32).sum()
+// settings shared by every flag during a step
+struct StepSettings<'a> {
+    wind: Vec2,
+    gust: Option<Gust>,
+    time: f32,
+    dt: f32,
+    clamp_mode: ClampMode,
+    link_solver: LinkSolver,
+    wind_fields: &'a [(WindId, WindField)],
+    obstacles: &'a [(ObstacleId, Obstacle)],
+    constraint_enabled: [bool; 3],
+    bounds: WorldBounds,
+    // fraction of its stiffness each flag uses, below 1.0 during the startup ramp
+    stiffness_scale: f32,
+}
+
+// the rectangle nodes are pushed back into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl WorldBounds {
+    // from the origin to `size`, like a window
+    pub fn from_size(size: Vec2) -> WorldBounds {
+        WorldBounds {
+            min: Vec2::ZERO,
+            max: size,
+        }
     }
 
-    pub fn select_nodes(&mut self, pos: Vec2) {
-        let radius = 10.0;
-        let in_range = self
-            .nodes
-            .iter()
-            .map(|n| n.pos.distance_squared(pos))
-            .enumerate()
-            .filter(|(_, d)| *d < radius * radius)
-            .map(|(i, _)| i)
-            .collect::<Vec<usize>>();
-        if !in_range.is_empty() {
-            self.selected_nodes = Some(in_range);
+    fn inset(self, margin: f32) -> WorldBounds {
+        WorldBounds {
+            min: self.min + margin,
+            max: self.max - margin,
+        }
+    }
+}
+
+// no walls at all, until the simulation is given some with `Physics::set_bounds`
+impl Default for WorldBounds {
+    fn default() -> Self {
+        WorldBounds {
+            min: Vec2::NEG_INFINITY,
+            max: Vec2::INFINITY,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ClampMode {
+    // clamp the velocity and the gravity contribution together
+    #[default]
+    Total,
+    // clamp only the velocity, gravity is added afterwards unclamped
+    Velocity,
+}
+
+// how the links of a flag are solved in each substep
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LinkSolver {
+    // one link after the other, each seeing the corrections of the ones before
+    #[default]
+    GaussSeidel,
+    // all the links at once from the same positions, see `Flag::apply_links_jacobi`
+    Jacobi,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    Uniform,
+    // fades linearly from full strength at `center` to nothing at `radius`
+    Radial { center: Vec2, radius: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindField {
+    pub force: Vec2,
+    pub falloff: Falloff,
+}
+
+impl WindField {
+    fn force_at(&self, pos: Vec2) -> Vec2 {
+        match self.falloff {
+            Falloff::Uniform => self.force,
+            Falloff::Radial { center, radius } => {
+                self.force * (1.0 - pos.distance(center) / radius).max(0.0)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindId(usize);
+
+// a solid disc the cloth drapes over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Obstacle {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObstacleId(usize);
+
+// Modulates the wind with waves travelling along it, so the cloth ripples instead of being
+// pushed as a whole. The wind strength swings by `amplitude` around its mean.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gust {
+    pub amplitude: f32,
+    // in waves per second
+    pub frequency: f32,
+    pub wavelength: f32,
+}
+
+impl Gust {
+    fn scale(&self, wind: Vec2, pos: Vec2, time: f32) -> f32 {
+        let along = pos.dot(wind.normalize_or_zero()) / self.wavelength;
+        1.0 + self.amplitude * (std::f32::consts::TAU * (along - self.frequency * time)).sin()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkId {
+    pub flag: usize,
+    pub link: usize,
+}
+
+// a node driven along `target`, evaluated at the simulation time
+struct Kinematic {
+    node: usize,
+    was_pinned: bool,
+    target: Box<dyn Fn(f32) -> Vec2 + Send>,
+}
+
+struct PinPath {
+    flag: usize,
+    points: Vec<Vec2>,
+    lengths: Vec<f32>,
+    duration: f32,
+    start_time: f32,
+    anchors: Vec<(usize, Vec2)>,
+}
+
+impl PinPath {
+    fn sample(&self, time: f32) -> Vec2 {
+        let total = *self.lengths.last().unwrap();
+        let t = ((time - self.start_time) / self.duration).clamp(0.0, 1.0);
+        let target = t * total;
+        let i = self.lengths.partition_point(|&l| l < target).max(1);
+        if i >= self.points.len() {
+            return *self.points.last().unwrap();
+        }
+        let seg = self.lengths[i] - self.lengths[i - 1];
+        let f = if seg > 0.0 {
+            (target - self.lengths[i - 1]) / seg
         } else {
-            self.selected_nodes = None
+            0.0
+        };
+        self.points[i - 1].lerp(self.points[i], f)
+    }
+}
+
+// state of the simulation after a step, passed to the metrics sink
+#[derive(Clone, Debug)]
+pub struct StepMetrics {
+    pub step_count: usize,
+    pub time: f32,
+    pub max_speed: f32,
+    pub kinetic_energy: f32,
+    pub torn_links: usize,
+    // duration of each phase of the step, in the order they ended, with the whole step last
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+type MetricsSink = Box<dyn FnMut(&StepMetrics) + Send>;
+
+// time spent in each phase of the steps timed since the last reset. The phases of the flags are
+// summed over the flags, which run in parallel, so they can add up to more than `flags`.
+#[derive(Clone, Debug, Default)]
+pub struct StepTimings {
+    pub steps: usize,
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl StepTimings {
+    fn add(&mut self, name: &'static str, duration: Duration) {
+        match self.phases.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((name, duration)),
         }
     }
 
-    pub fn move_selected_nodes(&mut self, pos: Vec2) {
-        match &self.selected_nodes {
-            None => (),
-            Some(nodes) => nodes.iter().for_each(|&i| {
-                self.nodes[i].pos = pos;
-            }),
+    // average duration of each phase over the timed steps
+    pub fn per_step(&self) -> impl Iterator<Item = (&'static str, Duration)> + '_ {
+        let steps = self.steps.max(1) as u32;
+        self.phases.iter().map(move |&(name, d)| (name, d / steps))
+    }
+}
+
+// a link with an end grabbed by the mouse is `Selected` even if the other one is pinned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkHighlight {
+    None,
+    Pinned,
+    Selected,
+}
+
+// a numeric health check of the solver, speeds being displacements over the last step
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimStats {
+    pub kinetic_energy: f32,
+    pub mean_strain: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    // nodes outside the bounds given to `Physics::set_bounds`, the margin not counted
+    pub out_of_bounds: usize,
+    // sub-steps run by the last `Physics::advance`
+    pub sub_steps: usize,
+}
+
+// the tunable settings of a simulation, without its state, so they can be saved and reapplied
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Params {
+    pub clamp_mode: ClampMode,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub link_solver: LinkSolver,
+    pub constraint_enabled: [bool; 3],
+    pub interaction_iterations: usize,
+    pub interaction_radius: f32,
+    pub max_grab_step: f32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub startup_ramp_steps: usize,
+    // one entry per flag
+    pub substeps: Vec<usize>,
+    pub pressure: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drag: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gravity: Vec<[f32; 2]>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stiffness: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_push: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damping: Vec<f32>,
+}
+
+#[derive(Clone)]
+pub struct FlagParams {
+    pub size: f32,
+    pub corner: Vec2,
+    pub width: usize,
+    pub height: usize,
+    // diagonal links across each cell, resisting shearing
+    pub shear: bool,
+    // links skipping a node, resisting folding
+    pub bend: bool,
+    pub gravity: Vec2,
+    // Fraction of a link's error each end corrects per solve, 0.5 projects the link exactly.
+    // Above 0.5 the ends overshoot, and past 1.0 every solve adds energy until the cloth blows
+    // up. Every substep solves the links once, so a stiffer cloth is better obtained with more
+    // `Physics::sub_steps` than with a stiffness over 0.5, which only holds up when they are
+    // plenty.
+    pub stiffness: f32,
+    // cap on the correction of compressed links relative to their length, letting the cloth
+    // crumple instead of pushing back
+    pub max_push: f32,
+    // Fraction of their velocity the nodes lose every substep. Without it the cloth keeps
+    // jittering forever, and a little of it also lets a stiff cloth settle instead of ringing.
+    pub damping: f32,
+    // mass of the nodes of the top and bottom rows, the rows in between are interpolated
+    pub mass: [f32; 2],
+    pub pinning: Pinning,
+    // silent steps run when the simulation is built, so the flag starts draped rather than flat
+    pub settle_steps: usize,
+}
+
+// which nodes of a flag start pinned
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum Pinning {
+    // the two ends of the left edge, where a flag meets its pole
+    #[default]
+    TopLeftCorners,
+    TopEdge,
+    FourCorners,
+    LeftEdge,
+    // node indices local to the flag, row by row from the top left corner
+    Custom(Vec<usize>),
+}
+
+impl FlagParams {
+    // whether a flag can be built from these, the fields being public they may be set directly
+    pub fn validate(&self) -> Result<(), String> {
+        // a single row or column of nodes has no links in the other direction
+        if self.width < 2 || self.height < 2 {
+            return Err(format!(
+                "a flag needs at least 2x2 nodes, got {}x{}",
+                self.width, self.height
+            ));
+        }
+        if self.size <= 0.0 || !self.size.is_finite() {
+            return Err(format!("the size must be positive, got {}", self.size));
+        }
+        if self.mass.iter().any(|&m| m <= 0.0 || !m.is_finite()) {
+            return Err(format!("node masses must be positive, got {:?}", self.mass));
+        }
+        if let Pinning::Custom(pinned) = &self.pinning {
+            if let Some(i) = pinned.iter().find(|&&i| i >= self.width * self.height) {
+                return Err(format!(
+                    "pinned node {} is outside the {}x{} flag",
+                    i, self.width, self.height
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn builder() -> FlagParamsBuilder {
+        FlagParamsBuilder {
+            params: FlagParams {
+                size: 100.0,
+                corner: Vec2::ZERO,
+                width: 0,
+                height: 0,
+                shear: false,
+                bend: false,
+                gravity: Vec2::ZERO,
+                stiffness: DEFAULT_STIFFNESS,
+                max_push: DEFAULT_MAX_PUSH,
+                damping: 0.0,
+                mass: [1.0; 2],
+                pinning: Pinning::default(),
+                settle_steps: 0,
+            },
+        }
+    }
+}
+
+// `FlagParams` with the defaults of a flag loaded from a file, only the grid has to be given
+pub struct FlagParamsBuilder {
+    params: FlagParams,
+}
+
+impl FlagParamsBuilder {
+    pub fn corner(mut self, corner: Vec2) -> Self {
+        self.params.corner = corner;
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.params.size = size;
+        self
+    }
+
+    pub fn grid(mut self, width: usize, height: usize) -> Self {
+        self.params.width = width;
+        self.params.height = height;
+        self
+    }
+
+    pub fn shear(mut self, shear: bool) -> Self {
+        self.params.shear = shear;
+        self
+    }
+
+    pub fn bend(mut self, bend: bool) -> Self {
+        self.params.bend = bend;
+        self
+    }
+
+    pub fn gravity(mut self, gravity: Vec2) -> Self {
+        self.params.gravity = gravity;
+        self
+    }
+
+    pub fn stiffness(mut self, stiffness: f32) -> Self {
+        self.params.stiffness = stiffness;
+        self
+    }
+
+    pub fn max_push(mut self, max_push: f32) -> Self {
+        self.params.max_push = max_push;
+        self
+    }
+
+    pub fn damping(mut self, damping: f32) -> Self {
+        self.params.damping = damping;
+        self
+    }
+
+    pub fn mass(mut self, top: f32, bottom: f32) -> Self {
+        self.params.mass = [top, bottom];
+        self
+    }
+
+    pub fn pinning(mut self, pinning: Pinning) -> Self {
+        self.params.pinning = pinning;
+        self
+    }
+
+    pub fn settle_steps(mut self, steps: usize) -> Self {
+        self.params.settle_steps = steps;
+        self
+    }
+
+    pub fn build(self) -> Result<FlagParams, String> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
+}
+
+// a link between nodes of two flags, with the flags it joins, see `Physics::stitch`
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Stitch {
+    link: Link,
+    flags: [usize; 2],
+}
+
+// the state written by `Physics::save`, the settings and the interaction are not saved
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct SavedState<'a> {
+    nodes: &'a [Node],
+    initial_nodes: &'a [Node],
+    flags: &'a [Flag],
+    stitches: &'a [Stitch],
+    time: f32,
+    step_count: usize,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct LoadedState {
+    nodes: Vec<Node>,
+    initial_nodes: Vec<Node>,
+    flags: Vec<Flag>,
+    #[serde(default)]
+    stitches: Vec<Stitch>,
+    time: f32,
+    step_count: usize,
+}
+
+pub struct Physics {
+    nodes: Vec<Node>,
+    // the nodes as the flags were built, restored by `reset`
+    initial_nodes: Vec<Node>,
+    flags: Vec<Flag>,
+    // index of the first node of every flag, to split `nodes` between the flags
+    breakpoints: Vec<usize>,
+    stitches: Vec<Stitch>,
+    // set when stitches or flags are added, the flags track their own tears and mends
+    links_changed: bool,
+    time: f32,
+    step_count: usize,
+    sub_steps: usize,
+    // bounds of the sub-step count when it follows the speed of the nodes
+    adaptive_sub_steps: Option<(usize, usize)>,
+    last_sub_steps: usize,
+    // Speed of the fastest node over the last `advance`, measured between its start and end.
+    // Over a single sub-step the link corrections, which don't shrink with it, would dominate.
+    advance_speed: f32,
+    advance_start: Vec<Vec2>,
+    pin_paths: Vec<PinPath>,
+    kinematic: Vec<Kinematic>,
+    clamp_mode: ClampMode,
+    link_solver: LinkSolver,
+    wind: Vec2,
+    gust: Option<Gust>,
+    wind_fields: Vec<(WindId, WindField)>,
+    next_wind_id: usize,
+    obstacles: Vec<(ObstacleId, Obstacle)>,
+    next_obstacle_id: usize,
+    interaction_iterations: usize,
+    interaction_radius: f32,
+    constraint_enabled: [bool; 3],
+    max_grab_step: f32,
+    bounds: WorldBounds,
+    boundary_margin: f32,
+    startup_ramp_steps: usize,
+    trace: Option<Trace>,
+    metrics_sink: Option<MetricsSink>,
+    phase_times: Vec<(&'static str, Duration)>,
+    step_timings: Option<StepTimings>,
+    // positions pinned nodes are expected at, only tracked while the pin check is enabled
+    pin_check: Option<Vec<Vec2>>,
+
+    // grabbed nodes with their offset from the grab point, kept while dragging
+    selected_nodes: Option<Vec<(usize, Vec2)>>,
+    // the nodes as they were when the last grab started, for `undo_grab`
+    grab_start: Option<Vec<Node>>,
+    select_radius: f32,
+    grab_falloff_radius: f32,
+    // nodes around the selection dragged along with it, with the fraction of the motion they get
+    grab_falloff: Vec<(usize, f32)>,
+    grab_point: Vec2,
+}
+
+impl Physics {
+    // Stepping is deterministic, no randomness is involved and every flag only moves its own
+    // nodes, so two simulations built and driven alike stay bit-identical whatever the number
+    // of threads solving the flags.
+    pub fn new(flag_sizes: &[FlagParams]) -> Physics {
+        Self::try_new(flag_sizes).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // `new`, failing on the first flag that doesn't pass `FlagParams::validate`
+    pub fn try_new(flag_sizes: &[FlagParams]) -> Result<Physics, String> {
+        for (i, fp) in flag_sizes.iter().enumerate() {
+            fp.validate().map_err(|e| format!("flag {}: {}", i, e))?;
+        }
+        let mut nodes =
+            vec![Node::default(); flag_sizes.iter().map(|fp| fp.width * fp.height).sum()];
+
+        let offsets = flag_sizes
+            .iter()
+            .scan(0, |acc, fp| {
+                let offset = *acc;
+                *acc += fp.width * fp.height;
+                Some(offset)
+            })
+            .collect::<Vec<usize>>();
+
+        let flags = flag_sizes
+            .iter()
+            .zip(offsets)
+            .map(|(fp, offset)| {
+                let nodes = &mut nodes[offset..(offset + fp.width * fp.height)];
+                let mut flag = Flag::new(nodes, offset, fp.corner, fp.size, fp.width, fp.height);
+                flag.gravity = fp.gravity;
+                flag.stiffness = fp.stiffness;
+                flag.max_push = fp.max_push;
+                flag.damping = fp.damping;
+                flag.set_mass(nodes, fp.mass);
+                flag.pin(nodes, &fp.pinning);
+                if fp.shear {
+                    flag.add_shear_links(nodes);
+                }
+                if fp.bend {
+                    flag.add_bend_links(nodes);
+                }
+                flag
+            })
+            .collect();
+
+        //    vec![Flag::new(&mut nodes, 0, vec2(100.0, 100.0), 100.0, 10, 10)];
+        let mut physics = Self::from_parts(nodes, flags);
+        let settle_steps = flag_sizes.iter().map(|fp| fp.settle_steps).max();
+        physics.settle(settle_steps.unwrap_or(0), SETTLE_DT);
+        Ok(physics)
+    }
+
+    // `rows` x `cols` copies of the flag of `params`, each one's corner `spacing` away from its
+    // neighbours', in a single simulation
+    pub fn grid_of_flags(rows: usize, cols: usize, spacing: Vec2, params: &FlagParams) -> Physics {
+        let flags = (0..rows)
+            .flat_map(|y| (0..cols).map(move |x| vec2(x as f32, y as f32)))
+            .map(|cell| FlagParams {
+                corner: params.corner + cell * spacing,
+                ..params.clone()
+            })
+            .collect::<Vec<FlagParams>>();
+        Self::new(&flags)
+    }
+
+    // loads the rest shape of a single `width` x `height` flag from the vertices of an OBJ file,
+    // which must be listed row by row starting from the top left corner
+    pub fn from_obj(path: impl AsRef<Path>, width: usize, height: usize) -> io::Result<Physics> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if width < 2 || height < 2 {
+            return Err(invalid(format!(
+                "a flag needs at least 2x2 nodes, got {}x{}",
+                width, height
+            )));
+        }
+
+        let mut nodes = vec![];
+        for line in fs::read_to_string(path)?.lines() {
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some("v") {
+                continue;
+            }
+            let mut coord = || -> io::Result<f32> {
+                fields
+                    .next()
+                    .ok_or_else(|| invalid(format!("missing coordinate in `{}`", line)))?
+                    .parse()
+                    .map_err(|e| invalid(format!("bad coordinate in `{}`: {}", line, e)))
+            };
+            // obj's y points up, the simulation's points down the screen, as in `export_obj`
+            let pos = vec2(coord()?, -coord()?);
+            nodes.push(Node {
+                pos,
+                last_pos: pos,
+                ..Default::default()
+            });
+        }
+
+        if nodes.len() != width * height {
+            return Err(invalid(format!(
+                "expected {}x{} = {} vertices, found {}",
+                width,
+                height,
+                width * height,
+                nodes.len()
+            )));
+        }
+
+        let flag = Flag::from_nodes(&mut nodes, 0, width, height);
+        Ok(Self::from_parts(nodes, vec![flag]))
+    }
+
+    // Builds a single flag from an image, every opaque pixel becoming a node `size / width` apart
+    // from its neighbours, and every opaque MASK_PIN_COLOR pixel a pinned one. The flag keeps the
+    // full grid of the image: the nodes of transparent pixels are absent, left out of the forces,
+    // the collisions, the pins and the picking, and the links reaching them are holes, so the
+    // gaps are neither solved nor drawn.
+    pub fn from_image_mask(path: impl AsRef<Path>, corner: Vec2, size: f32) -> io::Result<Physics> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mask = image::open(path)
+            .map_err(|e| invalid(e.to_string()))?
+            .to_rgba8();
+        let (width, height) = (mask.width() as usize, mask.height() as usize);
+        if width < 2 || height < 2 {
+            return Err(invalid(format!(
+                "a mask needs at least 2x2 pixels, got {}x{}",
+                width, height
+            )));
+        }
+        let opaque = mask
+            .as_raw()
+            .chunks_exact(4)
+            .map(|p| p[3] >= MASK_MIN_ALPHA)
+            .collect::<Vec<bool>>();
+        if !opaque.contains(&true) {
+            return Err(invalid("the mask has no opaque pixel".to_string()));
+        }
+
+        let mut nodes = vec![Node::default(); width * height];
+        let mut flag = Flag::new(&mut nodes, 0, corner, size, width, height);
+        for ((n, &opaque), p) in nodes
+            .iter_mut()
+            .zip(&opaque)
+            .zip(mask.as_raw().chunks_exact(4))
+        {
+            n.absent = !opaque;
+            n.pinned = opaque && p[..3] == MASK_PIN_COLOR;
+        }
+        flag.holes = (0..flag.links.len())
+            .filter(|&i| !opaque[flag.links[i].node1] || !opaque[flag.links[i].node2])
+            .collect();
+        flag.mend();
+        Ok(Self::from_parts(nodes, vec![flag]))
+    }
+
+    // writes the nodes and flags as JSON, to resume the simulation with `load`
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let state = SavedState {
+            nodes: &self.nodes,
+            initial_nodes: &self.initial_nodes,
+            flags: &self.flags,
+            stitches: &self.stitches,
+            time: self.time,
+            step_count: self.step_count,
+        };
+        fs::write(path, serde_json::to_string(&state)?)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Physics> {
+        let state: LoadedState = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut flags = state.flags;
+        for f in &mut flags {
+            f.offset_links = offset_links(&f.links, f.offset);
+        }
+        let mut physics = Self::from_parts(state.nodes, flags);
+        physics.initial_nodes = state.initial_nodes;
+        physics.stitches = state.stitches;
+        physics.time = state.time;
+        physics.step_count = state.step_count;
+        Ok(physics)
+    }
+
+    // writes the cloth as a wavefront OBJ at depth `z`, one group per flag and two faces per cell
+    pub fn export_obj(&self, path: impl AsRef<Path>, z: f32) -> io::Result<()> {
+        let mut obj = String::new();
+        for (f, &start) in self.breakpoints.iter().enumerate() {
+            let end = self
+                .breakpoints
+                .get(f + 1)
+                .copied()
+                .unwrap_or(self.nodes.len());
+            obj += &format!("g flag{}\n", f);
+            // the simulation's y points down the screen, obj's points up
+            for n in &self.nodes[start..end] {
+                obj += &format!("v {} {} {}\n", n.pos.x, -n.pos.y, z);
+            }
+            // obj indices are global and start at 1
+            for [a, b, c, d] in self.quads(f).map(|quad| quad.map(|i| i + 1)) {
+                obj += &format!("f {} {} {}\nf {} {} {}\n", a, b, c, a, c, d);
+            }
+        }
+        fs::write(path, obj)
+    }
+
+    fn from_parts(nodes: Vec<Node>, flags: Vec<Flag>) -> Physics {
+        Physics {
+            initial_nodes: nodes.clone(),
+            nodes,
+            breakpoints: flags.iter().map(|f| f.offset).collect(),
+            flags,
+            stitches: vec![],
+            links_changed: false,
+            time: 0.0,
+            step_count: 0,
+            sub_steps: DEFAULT_SUB_STEPS,
+            adaptive_sub_steps: None,
+            last_sub_steps: DEFAULT_SUB_STEPS,
+            advance_speed: 0.0,
+            advance_start: vec![],
+            pin_paths: vec![],
+            kinematic: vec![],
+            clamp_mode: ClampMode::default(),
+            link_solver: LinkSolver::default(),
+            wind: Vec2::ZERO,
+            gust: None,
+            wind_fields: vec![],
+            next_wind_id: 0,
+            obstacles: vec![],
+            next_obstacle_id: 0,
+            interaction_iterations: 0,
+            interaction_radius: 0.0,
+            constraint_enabled: [true; 3],
+            max_grab_step: f32::INFINITY,
+            bounds: WorldBounds::default(),
+            boundary_margin: 0.0,
+            startup_ramp_steps: 0,
+            trace: None,
+            metrics_sink: None,
+            phase_times: vec![],
+            step_timings: None,
+            pin_check: None,
+            selected_nodes: None,
+            grab_start: None,
+            select_radius: 10.0,
+            grab_falloff_radius: 0.0,
+            grab_falloff: vec![],
+            grab_point: Vec2::ZERO,
+        }
+    }
+
+    pub fn params(&self) -> Params {
+        Params {
+            clamp_mode: self.clamp_mode,
+            link_solver: self.link_solver,
+            constraint_enabled: self.constraint_enabled,
+            interaction_iterations: self.interaction_iterations,
+            interaction_radius: self.interaction_radius,
+            max_grab_step: self.max_grab_step,
+            startup_ramp_steps: self.startup_ramp_steps,
+            substeps: self.flags.iter().map(|f| f.substeps).collect(),
+            pressure: self.flags.iter().map(|f| f.pressure).collect(),
+            drag: self.flags.iter().map(|f| f.drag).collect(),
+            gravity: self.flags.iter().map(|f| f.gravity.into()).collect(),
+            stiffness: self.flags.iter().map(|f| f.stiffness).collect(),
+            max_push: self.flags.iter().map(|f| f.max_push).collect(),
+            damping: self.flags.iter().map(|f| f.damping).collect(),
+        }
+    }
+
+    // per flag values are matched by index, extra entries are ignored
+    pub fn set_params(&mut self, params: &Params) {
+        self.clamp_mode = params.clamp_mode;
+        self.link_solver = params.link_solver;
+        self.constraint_enabled = params.constraint_enabled;
+        self.interaction_iterations = params.interaction_iterations;
+        self.interaction_radius = params.interaction_radius;
+        self.max_grab_step = params.max_grab_step;
+        self.startup_ramp_steps = params.startup_ramp_steps;
+        for (f, &substeps) in self.flags.iter_mut().zip(&params.substeps) {
+            f.substeps = substeps.max(1);
+        }
+        for (f, &pressure) in self.flags.iter_mut().zip(&params.pressure) {
+            f.pressure = pressure;
+        }
+        for (f, &drag) in self.flags.iter_mut().zip(&params.drag) {
+            f.drag = drag;
+        }
+        for (f, &gravity) in self.flags.iter_mut().zip(&params.gravity) {
+            f.gravity = gravity.into();
+        }
+        for (f, &stiffness) in self.flags.iter_mut().zip(&params.stiffness) {
+            f.stiffness = stiffness;
+        }
+        for (f, &max_push) in self.flags.iter_mut().zip(&params.max_push) {
+            f.max_push = max_push;
+        }
+        for (f, &damping) in self.flags.iter_mut().zip(&params.damping) {
+            f.damping = damping;
+        }
+    }
+
+    // Appends the nodes and flags of `other`, which keeps its own flag order after ours. Only the
+    // flags, their pin paths and kinematic nodes are taken, the settings of `self` apply to the
+    // merged flags.
+    pub fn merge(&mut self, other: Physics) {
+        let node_offset = self.nodes.len();
+        let flag_offset = self.flags.len();
+
+        self.nodes.extend(other.nodes);
+        self.initial_nodes.extend(other.initial_nodes);
+        self.flags.extend(other.flags.into_iter().map(|mut f| {
+            f.offset += node_offset;
+            for l in &mut f.offset_links {
+                l.node1 += node_offset;
+                l.node2 += node_offset;
+            }
+            f
+        }));
+        self.breakpoints = self.flags.iter().map(|f| f.offset).collect();
+        self.links_changed = true;
+        self.stitches
+            .extend(other.stitches.into_iter().map(|mut s| {
+                s.link.node1 += node_offset;
+                s.link.node2 += node_offset;
+                s.flags = s.flags.map(|f| f + flag_offset);
+                s
+            }));
+        self.pin_paths
+            .extend(other.pin_paths.into_iter().map(|mut p| {
+                p.flag += flag_offset;
+                for (i, _) in &mut p.anchors {
+                    *i += node_offset;
+                }
+                p
+            }));
+        self.kinematic
+            .extend(other.kinematic.into_iter().map(|mut k| {
+                k.node += node_offset;
+                k
+            }));
+        self.snapshot_pins();
+    }
+
+    // Runs `steps` steps of `dt` and makes the result the starting point, which `reset` returns
+    // to. The clock starts over, as if the flags had been built that way.
+    pub fn settle(&mut self, steps: usize, dt: f32) {
+        if steps == 0 {
+            return;
+        }
+        for _ in 0..steps {
+            self.step(dt);
+        }
+        self.initial_nodes.clone_from(&self.nodes);
+        self.time = 0.0;
+        self.step_count = 0;
+    }
+
+    // Puts every node back where the flags were built and mends the torn links. The time starts
+    // over, so pin paths and kinematic nodes restart too, and the selection is dropped.
+    pub fn reset(&mut self) {
+        self.nodes.clone_from(&self.initial_nodes);
+        for k in &self.kinematic {
+            self.nodes[k.node].pinned = true;
+        }
+        for f in &mut self.flags {
+            if f.num_torn() > 0 {
+                f.mend();
+            }
+        }
+        self.time = 0.0;
+        self.step_count = 0;
+        self.selected_nodes = None;
+        self.grab_start = None;
+        self.grab_falloff.clear();
+        self.move_pin_paths();
+        self.move_kinematic();
+        self.snapshot_pins();
+    }
+
+    pub fn clamp_mode(&self) -> ClampMode {
+        self.clamp_mode
+    }
+
+    pub fn set_clamp_mode(&mut self, mode: ClampMode) {
+        self.clamp_mode = mode;
+    }
+
+    pub fn link_solver(&self) -> LinkSolver {
+        self.link_solver
+    }
+
+    // Jacobi takes precedence over the batched links
+    pub fn set_link_solver(&mut self, solver: LinkSolver) {
+        self.link_solver = solver;
+    }
+
+    pub fn set_link_length_cache(&mut self, enabled: bool) {
+        self.flags.iter_mut().for_each(|f| {
+            f.link_lengths = if enabled {
+                vec![0.0; f.links.len()]
+            } else {
+                vec![]
+            }
+        });
+    }
+
+    // With the cache enabled this is the length measured by the solver during the last step,
+    // before its correction was applied. Otherwise it is computed from the current positions.
+    pub fn link_length(&self, flag: usize, link: usize) -> f32 {
+        let f = &self.flags[flag];
+        if f.link_lengths.is_empty() {
+            let l = &f.offset_links[link];
+            self.nodes[l.node1].pos.distance(self.nodes[l.node2].pos)
+        } else {
+            f.link_lengths[link]
+        }
+    }
+
+    // Solves the links of each flag in SIMD batches, grouped by a coloring so that no two links
+    // of a batch move the same node. Meant for very large flags, where it outweighs the cost of
+    // gathering and scattering the positions.
+    pub fn set_batched_links(&mut self, enabled: bool) {
+        self.flags.iter_mut().for_each(|f| {
+            f.link_colors = if enabled {
+                color_links(&f.links, f.width * f.height)
+            } else {
+                vec![]
+            }
+        });
+    }
+
+    pub fn constraint_enabled(&self, kind: ConstraintKind) -> bool {
+        self.constraint_enabled[kind as usize]
+    }
+
+    pub fn set_constraint_enabled(&mut self, kind: ConstraintKind, enabled: bool) {
+        self.constraint_enabled[kind as usize] = enabled;
+    }
+
+    pub fn set_bounds(&mut self, bounds: WorldBounds) {
+        self.bounds = bounds;
+    }
+
+    // keeps nodes `margin` inside the bounds, so the cloth doesn't clip at the window edges
+    pub fn set_boundary_margin(&mut self, margin: f32) {
+        self.boundary_margin = margin;
+    }
+
+    // Eases a cloth that isn't at rest into shape by growing the stiffness linearly over the
+    // first `steps` steps, instead of snapping it back at full strength on the first one.
+    pub fn set_startup_ramp_steps(&mut self, steps: usize) {
+        self.startup_ramp_steps = steps;
+    }
+
+    fn stiffness_scale(&self) -> f32 {
+        if self.step_count >= self.startup_ramp_steps {
+            1.0
+        } else {
+            self.step_count as f32 / self.startup_ramp_steps as f32
+        }
+    }
+
+    // link stiffness of the flag used by the current step, below its target while the startup
+    // ramp lasts
+    pub fn stiffness(&self, flag: usize) -> f32 {
+        self.flags[flag].stiffness * self.stiffness_scale()
+    }
+
+    // see `FlagParams::stiffness` for the range that stays stable
+    pub fn set_stiffness(&mut self, flag: usize, stiffness: f32) {
+        self.flags[flag].stiffness = stiffness;
+    }
+
+    pub fn set_max_push(&mut self, flag: usize, max_push: f32) {
+        self.flags[flag].max_push = max_push;
+    }
+
+    pub fn set_gravity(&mut self, flag: usize, gravity: Vec2) {
+        self.flags[flag].gravity = gravity;
+    }
+
+    // fraction of its velocity a node of the flag loses every substep, unless it has its own
+    // damping, see `FlagParams::damping`
+    pub fn set_damping(&mut self, flag: usize, damping: f32) {
+        self.flags[flag].damping = damping;
+    }
+
+    pub fn set_node_damping(&mut self, node: usize, damping: f32) {
+        self.nodes[node].damping = Some(damping);
+    }
+
+    // gives every node within `radius` of `center` its own damping
+    pub fn set_damping_brush(&mut self, center: Vec2, radius: f32, damping: f32) {
+        self.nodes
+            .iter_mut()
+            .filter(|n| n.pos.distance_squared(center) < radius * radius)
+            .for_each(|n| n.damping = Some(damping));
+    }
+
+    pub fn set_interaction_solve(&mut self, extra_iterations: usize, radius: f32) {
+        self.interaction_iterations = extra_iterations;
+        self.interaction_radius = radius;
+    }
+
+    // links with an end within `interaction_radius` of a selected node, as (flag, link) pairs
+    fn interaction_links(&self) -> Vec<(usize, usize)> {
+        let Some(selected) = &self.selected_nodes else {
+            return vec![];
+        };
+        let centers = selected
+            .iter()
+            .map(|&(i, _)| self.nodes[i].pos)
+            .collect::<Vec<Vec2>>();
+        let r2 = self.interaction_radius * self.interaction_radius;
+        let near = |i: usize| {
+            centers
+                .iter()
+                .any(|c| c.distance_squared(self.nodes[i].pos) < r2)
+        };
+
+        self.flags
+            .iter()
+            .enumerate()
+            .flat_map(|(f, flag)| {
+                flag.offset_links
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, l)| !l.dead && self.constraint_enabled[l.kind as usize])
+                    .filter(|(_, l)| near(l.node1) || near(l.node2))
+                    .map(move |(i, _)| (f, i))
+            })
+            .collect()
+    }
+
+    fn apply_interaction_links(&mut self) {
+        if self.interaction_iterations == 0 {
+            return;
+        }
+        let links = self.interaction_links();
+        for _ in 0..self.interaction_iterations {
+            for &(f, i) in &links {
+                let stiffness = self.stiffness(f);
+                let flag = &self.flags[f];
+                solve_link(
+                    &mut self.nodes,
+                    &flag.offset_links[i],
+                    stiffness,
+                    flag.max_push,
+                );
+            }
+        }
+    }
+
+    // Joins `edge_a` of `flag_a` to `edge_b` of `flag_b` node by node, from left to right or top
+    // to bottom, with structural links holding the edges one node spacing of `flag_a` apart, as
+    // the neighbouring columns or rows of a single flag. The edges need the same number of nodes.
+    //
+    // The flags are solved in parallel, each on its own chunk of the nodes, and a stitch reaches
+    // into two chunks, so the stitches are solved once all the flags have stepped, serially and
+    // once per step whatever the substeps of the flags. They are a step behind the links within
+    // the flags and a little softer, and they keep the step deterministic. They don't tear.
+    pub fn stitch(
+        &mut self,
+        flag_a: usize,
+        edge_a: Edge,
+        flag_b: usize,
+        edge_b: Edge,
+    ) -> Result<(), String> {
+        let num_flags = self.flags.len();
+        if let Some(f) = [flag_a, flag_b].into_iter().find(|&f| f >= num_flags) {
+            return Err(format!("no flag {}, there are {}", f, num_flags));
+        }
+        if flag_a == flag_b {
+            return Err(format!("flag {} can't be stitched to itself", flag_a));
+        }
+        let (a, b) = (&self.flags[flag_a], &self.flags[flag_b]);
+        let len = a.edge_len(edge_a);
+        if b.edge_len(edge_b) != len {
+            return Err(format!(
+                "{:?} edge of flag {} has {} nodes, {:?} edge of flag {} has {}",
+                edge_a,
+                flag_a,
+                len,
+                edge_b,
+                flag_b,
+                b.edge_len(edge_b)
+            ));
+        }
+        let spacing = self.initial_nodes[a.edge_node(edge_a, 0)]
+            .pos
+            .distance(self.initial_nodes[a.edge_node(edge_a, 1)].pos);
+        let stitches = (0..len).map(|i| Stitch {
+            link: Link {
+                node1: a.edge_node(edge_a, i),
+                node2: b.edge_node(edge_b, i),
+                resting_distance: spacing,
+                kind: ConstraintKind::Structural,
+                dead: false,
+            },
+            flags: [flag_a, flag_b],
+        });
+        self.stitches.extend(stitches);
+        self.links_changed = true;
+        Ok(())
+    }
+
+    // a stitch is as stiff and pushes as far as the weaker of its two flags allows
+    fn apply_stitches(&mut self, stiffness_scale: f32) {
+        if !self.constraint_enabled[ConstraintKind::Structural as usize] {
+            return;
+        }
+        for s in &self.stitches {
+            let [a, b] = s.flags.map(|f| &self.flags[f]);
+            let stiffness = a.stiffness.min(b.stiffness) * stiffness_scale;
+            solve_link(
+                &mut self.nodes,
+                &s.link,
+                stiffness,
+                a.max_push.min(b.max_push),
+            );
+        }
+    }
+
+    fn move_pin_paths(&mut self) {
+        if self.pin_paths.is_empty() {
+            return;
+        }
+        for path in &self.pin_paths {
+            let anchor = path.sample(self.time);
+            for &(i, offset) in &path.anchors {
+                let n = &mut self.nodes[i];
+                n.last_pos = n.pos;
+                n.pos = anchor + offset;
+            }
+        }
+        self.snapshot_pins();
+    }
+
+    // Places `node` exactly on `target(time)` at the start of every step, so forces and links
+    // have no effect on it while its neighbours are pulled along.
+    pub fn set_kinematic(&mut self, node: usize, target: impl Fn(f32) -> Vec2 + Send + 'static) {
+        self.clear_kinematic(node);
+        let was_pinned = self.nodes[node].pinned;
+        self.kinematic.push(Kinematic {
+            node,
+            was_pinned,
+            target: Box::new(target),
+        });
+        self.nodes[node].pinned = true;
+        self.move_kinematic();
+    }
+
+    // releases the node, which gets its previous pinning back
+    pub fn clear_kinematic(&mut self, node: usize) {
+        if let Some(i) = self.kinematic.iter().position(|k| k.node == node) {
+            let k = self.kinematic.remove(i);
+            self.nodes[node].pinned = k.was_pinned;
+        }
+    }
+
+    // Moves every pinned node to `target(node, time)`, for animating them from outside each frame.
+    // The solver keeps them where they are put, and the move counts as their velocity.
+    pub fn drive_pinned(&mut self, target: impl Fn(usize, f32) -> Vec2, time: f32) {
+        for (i, n) in self.nodes.iter_mut().enumerate().filter(|(_, n)| n.pinned) {
+            n.last_pos = n.pos;
+            n.pos = target(i, time);
+        }
+        self.snapshot_pins();
+    }
+
+    fn move_kinematic(&mut self) {
+        if self.kinematic.is_empty() {
+            return;
+        }
+        for k in &self.kinematic {
+            let n = &mut self.nodes[k.node];
+            n.last_pos = n.pos;
+            n.pos = (k.target)(self.time);
+        }
+        self.snapshot_pins();
+    }
+
+    // Debug guard for the pinning invariant: nothing but pin paths, grabbing and re-pinning
+    // should move a pinned node, any other displacement found after a step is logged.
+    pub fn set_pin_check(&mut self, enabled: bool) {
+        self.pin_check = enabled.then(Vec::new);
+        self.snapshot_pins();
+    }
+
+    fn snapshot_pins(&mut self) {
+        if let Some(positions) = &mut self.pin_check {
+            positions.clear();
+            positions.extend(self.nodes.iter().map(|n| n.pos));
+        }
+    }
+
+    fn check_pins(&mut self) {
+        let Some(positions) = &mut self.pin_check else {
+            return;
+        };
+        for (i, (n, expected)) in self.nodes.iter().zip(positions.iter_mut()).enumerate() {
+            if n.pinned && n.pos != *expected {
+                eprintln!(
+                    "pinned node {} moved from {:?} to {:?} at step {}",
+                    i, expected, n.pos, self.step_count
+                );
+                *expected = n.pos;
+            }
+        }
+    }
+
+    // a force applied to every node like gravity, modulated by `gust` when it is set
+    pub fn set_wind(&mut self, wind: Vec2, gust: Option<Gust>) {
+        self.wind = wind;
+        self.gust = gust;
+    }
+
+    pub fn add_wind_field(&mut self, field: WindField) -> WindId {
+        let id = WindId(self.next_wind_id);
+        self.next_wind_id += 1;
+        self.wind_fields.push((id, field));
+        id
+    }
+
+    pub fn remove_wind_field(&mut self, id: WindId) {
+        self.wind_fields.retain(|(i, _)| *i != id);
+    }
+
+    // Keeps the nodes of each flag apart so a folding cloth doesn't pass through itself. Flags
+    // don't collide with each other.
+    pub fn enable_self_collision(&mut self, enabled: bool) {
+        for f in &mut self.flags {
+            f.collision = enabled.then(|| SpatialHash::new(f.collision_radius()));
+        }
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) -> ObstacleId {
+        let id = ObstacleId(self.next_obstacle_id);
+        self.next_obstacle_id += 1;
+        self.obstacles.push((id, obstacle));
+        id
+    }
+
+    pub fn obstacle(&self, id: ObstacleId) -> Option<Obstacle> {
+        self.obstacles
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, o)| *o)
+    }
+
+    pub fn move_obstacle(&mut self, id: ObstacleId, center: Vec2) {
+        if let Some((_, o)) = self.obstacles.iter_mut().find(|(i, _)| *i == id) {
+            o.center = center;
+        }
+    }
+
+    pub fn remove_obstacle(&mut self, id: ObstacleId) {
+        self.obstacles.retain(|(i, _)| *i != id);
+    }
+
+    pub fn set_pressure(&mut self, flag: usize, p: f32) {
+        self.flags[flag].pressure = p;
+    }
+
+    // coefficient of the air resistance of the flag's outline, see `Flag::apply_drag`
+    pub fn set_drag(&mut self, flag: usize, drag: f32) {
+        self.flags[flag].drag = drag;
+    }
+
+    // Curves the rest shape of the flag by making its rows longer from top to bottom, so it
+    // settles into an arc instead of lying flat. `amount` is the difference between the bottom
+    // and top row lengths relative to the flat ones, and must stay within -2.0..2.0.
+    pub fn set_camber(&mut self, flag: usize, amount: f32) {
+        let f = &mut self.flags[flag];
+        // horizontal links come first, row by row
+        for i in 0..(f.width - 1) * f.height {
+            let y = i / (f.width - 1);
+            let scale = f.camber_scale(amount, y) / f.camber_scale(f.camber, y);
+            f.links[i].resting_distance *= scale;
+            f.offset_links[i].resting_distance *= scale;
+        }
+        f.camber = amount;
+    }
+
+    pub fn set_substeps(&mut self, flag: usize, substeps: usize) {
+        self.flags[flag].substeps = substeps.max(1);
+    }
+
+    pub fn sub_steps(&self) -> usize {
+        self.sub_steps
+    }
+
+    // fewer sub-steps run faster, more keep a stiff cloth from stretching
+    pub fn set_sub_steps(&mut self, sub_steps: usize) {
+        self.sub_steps = sub_steps.max(1);
+    }
+
+    // With bounds, `advance` picks the sub-step count every call from the speed of the fastest
+    // node instead of using `sub_steps`, few when the cloth is at rest and more when it moves fast.
+    pub fn set_adaptive_sub_steps(&mut self, bounds: Option<(usize, usize)>) {
+        self.adaptive_sub_steps = bounds.map(|(min, max)| (min.max(1), max.max(min).max(1)));
+    }
+
+    pub fn adaptive_sub_steps(&self) -> Option<(usize, usize)> {
+        self.adaptive_sub_steps
+    }
+
+    // moves the simulation `dt` forward in `sub_steps` steps, or adaptive ones
+    pub fn advance(&mut self, dt: f32) {
+        let sub_steps = match self.adaptive_sub_steps {
+            Some((min, max)) => self.needed_sub_steps(dt).clamp(min, max),
+            None => self.sub_steps,
+        };
+        self.last_sub_steps = sub_steps;
+        if self.adaptive_sub_steps.is_some() {
+            let mut start = std::mem::take(&mut self.advance_start);
+            self.write_points(&mut start);
+            self.advance_start = start;
+        }
+        for _ in 0..sub_steps {
+            self.step(dt / sub_steps as f32);
+        }
+        if self.adaptive_sub_steps.is_some() {
+            let travel = self
+                .nodes
+                .iter()
+                .zip(&self.advance_start)
+                .map(|(n, start)| n.pos.distance(*start))
+                .fold(0.0, f32::max);
+            self.advance_speed = travel / dt;
+        }
+    }
+
+    // sub-steps over `dt` for the nodes to keep within `ADAPTIVE_MAX_TRAVEL` at their last speed
+    fn needed_sub_steps(&self, dt: f32) -> usize {
+        let min_rest = self
+            .alive_links()
+            .map(|l| l.resting_distance)
+            .fold(f32::INFINITY, f32::min);
+        // without links the shortest one is infinitely long, which gives 0
+        (self.advance_speed * dt / (min_rest * ADAPTIVE_MAX_TRAVEL)).ceil() as usize
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        let step_start = Instant::now();
+        self.time += dt;
+        self.step_count += 1;
+        self.move_pin_paths();
+        self.move_kinematic();
+        self.end_phase("pin_paths", step_start);
+
+        let start = Instant::now();
+        let stiffness_scale = self.stiffness_scale();
+        let chunks: ChunksMutIndices<'_, Node> =
+            ChunksMutIndices::new(&mut self.nodes, &self.breakpoints);
+
+        let settings = StepSettings {
+            wind: self.wind,
+            gust: self.gust,
+            time: self.time,
+            dt,
+            clamp_mode: self.clamp_mode,
+            link_solver: self.link_solver,
+            wind_fields: &self.wind_fields,
+            obstacles: &self.obstacles,
+            constraint_enabled: self.constraint_enabled,
+            bounds: self.bounds.inset(self.boundary_margin),
+            stiffness_scale,
+        };
+        self.flags
+            .iter_mut()
+            .zip(chunks)
+            .par_bridge()
+            .for_each(|(flag, (nodes, _))| flag.step(nodes, &settings));
+        self.end_phase("flags", start);
+
+        let start = Instant::now();
+        self.apply_stitches(stiffness_scale);
+        self.end_phase("stitches", start);
+        if let Some(timings) = &mut self.step_timings {
+            timings.steps += 1;
+            for f in &mut self.flags {
+                // flags merged since the timing started are timed from the next step
+                let times = f
+                    .phase_times
+                    .get_or_insert([Duration::ZERO; FLAG_PHASES.len()]);
+                for (name, time) in FLAG_PHASES.iter().zip(times.iter_mut()) {
+                    timings.add(name, std::mem::take(time));
+                }
+            }
+        }
+
+        let start = Instant::now();
+        self.apply_interaction_links();
+        self.end_phase("interaction", start);
+
+        let start = Instant::now();
+        self.check_pins();
+        self.end_phase("pin_check", start);
+        self.end_phase("step", step_start);
+        self.report_metrics();
+    }
+
+    // records how long each phase of `step` takes, until `take_trace`
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace::new());
+    }
+
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    // Calls `sink` at the end of every step with the state of the simulation and the time
+    // spent in each phase, for monitoring headless runs.
+    pub fn set_metrics_sink(&mut self, sink: impl FnMut(&StepMetrics) + Send + 'static) {
+        self.metrics_sink = Some(Box::new(sink));
+    }
+
+    // Accumulates the time spent in each phase of `step`, including the phases of the flags,
+    // until `take_step_timings`. Timing a phase costs a clock read, so it is off by default.
+    pub fn set_step_timing(&mut self, enabled: bool) {
+        self.step_timings = enabled.then(StepTimings::default);
+        for f in &mut self.flags {
+            f.phase_times = enabled.then_some([Duration::ZERO; FLAG_PHASES.len()]);
+        }
+    }
+
+    // the timings accumulated since the last call, empty unless the step timing is enabled
+    pub fn take_step_timings(&mut self) -> StepTimings {
+        self.step_timings
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // `step`, returning how long each of its phases took
+    pub fn step_timed(&mut self, dt: f32) -> StepTimings {
+        let enabled = self.step_timings.is_some();
+        let earlier = self.take_step_timings();
+        self.set_step_timing(true);
+        self.step(dt);
+        let timings = self.take_step_timings();
+        self.set_step_timing(enabled);
+        if let Some(t) = &mut self.step_timings {
+            *t = earlier;
+            t.steps += timings.steps;
+            for &(name, d) in &timings.phases {
+                t.add(name, d);
+            }
+        }
+        timings
+    }
+
+    fn end_phase(&mut self, name: &'static str, start: Instant) {
+        if let Some(trace) = &mut self.trace {
+            trace.span(name, start);
+        }
+        if self.metrics_sink.is_some() {
+            self.phase_times.push((name, start.elapsed()));
+        }
+        if let Some(timings) = &mut self.step_timings {
+            timings.add(name, start.elapsed());
+        }
+    }
+
+    fn report_metrics(&mut self) {
+        if self.metrics_sink.is_none() {
+            return;
+        }
+        let metrics = StepMetrics {
+            step_count: self.step_count,
+            time: self.time,
+            max_speed: self.max_speed(),
+            kinetic_energy: self.kinetic_energy(),
+            torn_links: self.num_torn_links(),
+            phases: std::mem::take(&mut self.phase_times),
+        };
+        if let Some(sink) = &mut self.metrics_sink {
+            sink(&metrics);
+        }
+        // keeps the allocation for the next step
+        self.phase_times = metrics.phases;
+        self.phase_times.clear();
+    }
+
+    pub fn pin_edge_span(&mut self, flag: usize, edge: Edge, range: Range<usize>) {
+        let f = &self.flags[flag];
+        let end = range.end.min(f.edge_len(edge));
+        for i in range.start..end {
+            self.nodes[f.edge_node(edge, i)].pinned = true;
+        }
+        self.snapshot_pins();
+    }
+
+    pub fn set_pinned(&mut self, node: usize, pinned: bool) {
+        self.nodes[node].pinned = pinned;
+        self.snapshot_pins();
+    }
+
+    pub fn is_pinned(&self, node: usize) -> bool {
+        self.nodes[node].pinned
+    }
+
+    // whether the node is a gap in the cloth, see `from_image_mask`
+    pub fn is_absent(&self, node: usize) -> bool {
+        self.nodes[node].absent
+    }
+
+    // the nodes held by the mouse, empty when nothing is grabbed
+    pub fn selected_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.selected_nodes.iter().flatten().map(|&(i, _)| i)
+    }
+
+    // pins the node closest to `pos` and returns it
+    pub fn pin_nearest(&mut self, pos: Vec2) -> Option<usize> {
+        let node = self.nearest_node(pos)?;
+        self.set_pinned(node, true);
+        Some(node)
+    }
+
+    pub fn set_pin_path(&mut self, flag: usize, points: Vec<Vec2>, duration: f32) {
+        self.pin_paths.retain(|p| p.flag != flag);
+        if points.is_empty() {
+            return;
+        }
+
+        let f = &self.flags[flag];
+        let pinned = (f.offset..(f.offset + f.width * f.height))
+            .filter(|&i| self.nodes[i].pinned)
+            .collect::<Vec<usize>>();
+        if pinned.is_empty() {
+            return;
+        }
+        let center = pinned.iter().map(|&i| self.nodes[i].pos).sum::<Vec2>() / pinned.len() as f32;
+
+        let lengths = std::iter::once(0.0)
+            .chain(points.windows(2).scan(0.0, |acc, w| {
+                *acc += w[0].distance(w[1]);
+                Some(*acc)
+            }))
+            .collect();
+
+        self.pin_paths.push(PinPath {
+            flag,
+            anchors: pinned
+                .into_iter()
+                .map(|i| (i, self.nodes[i].pos - center))
+                .collect(),
+            points,
+            lengths,
+            duration: duration.max(f32::EPSILON),
+            start_time: self.time,
+        });
+    }
+
+    pub fn tear_link(&mut self, flag: usize, link: usize) {
+        let f = &mut self.flags[flag];
+        f.links[link].dead = true;
+        f.offset_links[link].dead = true;
+        f.links_changed = true;
+    }
+
+    // tears every intact link crossing the segment from `from` to `to`, returns how many
+    pub fn cut_links(&mut self, from: Vec2, to: Vec2) -> usize {
+        let crossed = self
+            .flags
+            .iter()
+            .enumerate()
+            .flat_map(|(flag, f)| {
+                f.offset_links
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, l)| !l.dead)
+                    .map(move |(link, l)| (flag, link, l))
+            })
+            .filter(|(_, _, l)| {
+                let (a, b) = (self.nodes[l.node1].pos, self.nodes[l.node2].pos);
+                segments_cross(from, to, a, b)
+            })
+            .map(|(flag, link, _)| (flag, link))
+            .collect::<Vec<_>>();
+        for &(flag, link) in &crossed {
+            self.tear_link(flag, link);
+        }
+        crossed.len()
+    }
+
+    // `factor` times the rest length is how far links of the flag stretch before tearing
+    pub fn set_tear_factor(&mut self, flag: usize, factor: f32) {
+        self.flags[flag].tear_factor = factor;
+    }
+
+    // Whether links were torn, cut, mended, stitched or merged in since the last call, which
+    // invalidates `get_indices`. Only stitches and merges raise `link_capacity`.
+    pub fn take_links_changed(&mut self) -> bool {
+        let mut changed = std::mem::take(&mut self.links_changed);
+        for f in &mut self.flags {
+            changed |= std::mem::take(&mut f.links_changed);
+        }
+        changed
+    }
+
+    // links torn, cut or stretched past their tear factor, the holes of a mask left out
+    pub fn num_torn_links(&self) -> usize {
+        self.flags.iter().map(|f| f.num_torn()).sum()
+    }
+
+    pub fn dead_links(&self) -> Vec<LinkId> {
+        self.flags
+            .iter()
+            .enumerate()
+            .flat_map(|(flag, f)| {
+                f.links
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, l)| l.dead)
+                    .map(move |(link, _)| LinkId { flag, link })
+            })
+            .collect()
+    }
+
+    pub fn nearest_node(&self, pos: Vec2) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.absent)
+            .map(|(i, n)| (i, n.pos.distance_squared(pos)))
+            .min_by(|x, y| x.1.total_cmp(&y.1))
+            .map(|(i, _)| i)
+    }
+
+    // the (flag, link, distance) of the intact link whose segment passes closest to `pos`
+    pub fn nearest_link(&self, pos: Vec2) -> Option<(usize, usize, f32)> {
+        self.flags
+            .iter()
+            .enumerate()
+            .flat_map(|(f, flag)| {
+                flag.offset_links
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, l)| !l.dead)
+                    .map(move |(i, l)| (f, i, l))
+            })
+            .map(|(f, i, l)| {
+                let (a, b) = (self.nodes[l.node1].pos, self.nodes[l.node2].pos);
+                let ab = b - a;
+                let t = ((pos - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+                // a collapsed link gives a NaN `t`, measured from its first end instead
+                let closest = if t.is_nan() { a } else { a + ab * t };
+                (f, i, closest.distance(pos))
+            })
+            .min_by(|x, y| x.2.total_cmp(&y.2))
+    }
+
+    // the links of the flags which aren't torn, followed by the stitches between flags
+    fn alive_links(&self) -> impl Iterator<Item = &Link> {
+        self.flags
+            .iter()
+            .flat_map(|f| f.alive_links())
+            .chain(self.stitches.iter().map(|s| &s.link))
+    }
+
+    pub fn get_indices(&self) -> Vec<u32> {
+        self.alive_links()
+            .flat_map(|l| [l.node1 as u32, l.node2 as u32])
+            .collect()
+    }
+
+    pub fn get_points(&self) -> Vec<Vec2> {
+        self.nodes.iter().map(|n| n.pos).collect()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    // puts back nodes taken from `nodes`, there must be as many
+    pub fn restore_nodes(&mut self, nodes: &[Node]) {
+        self.nodes.copy_from_slice(nodes);
+        self.snapshot_pins();
+    }
+
+    // like `get_points`, but reusing the allocation of `out`
+    pub fn write_points(&self, out: &mut Vec<Vec2>) {
+        out.clear();
+        out.extend(self.nodes.iter().map(|n| n.pos));
+    }
+
+    // like `get_indices`, but reusing the allocation of `out`
+    pub fn write_indices(&self, out: &mut Vec<u32>) {
+        out.clear();
+        out.extend(
+            self.alive_links()
+                .flat_map(|l| [l.node1 as u32, l.node2 as u32]),
+        );
+    }
+
+    pub fn quads(&self, flag: usize) -> impl Iterator<Item = [usize; 4]> + '_ {
+        let f = &self.flags[flag];
+        f.cells().map(|quad| quad.map(|i| i + f.offset))
+    }
+
+    // two triangles for every intact cell, indexing the nodes like `get_points`
+    pub fn get_triangle_indices(&self) -> Vec<u32> {
+        (0..self.flags.len())
+            .flat_map(|f| self.quads(f))
+            .flat_map(|[a, b, c, d]| [a, b, c, a, c, d])
+            .map(|i| i as u32)
+            .collect()
+    }
+
+    // texture coordinates of every node, spanning 0..1 across each flag
+    pub fn get_uvs(&self) -> Vec<Vec2> {
+        self.flags
+            .iter()
+            .flat_map(|f| {
+                let scale = vec2(
+                    1.0 / (f.width - 1).max(1) as f32,
+                    1.0 / (f.height - 1).max(1) as f32,
+                );
+                (0..f.height)
+                    .flat_map(move |y| (0..f.width).map(move |x| vec2(x as f32, y as f32) * scale))
+            })
+            .collect()
+    }
+
+    // every quad as two separate triangles, each pulled towards its centroid by `shrink`
+    pub fn get_exploded_triangles(&self, shrink: f32) -> (Vec<Vec2>, Vec<u32>) {
+        let vertices = (0..self.flags.len())
+            .flat_map(|f| self.quads(f))
+            .flat_map(|[a, b, c, d]| [[a, b, c], [a, c, d]])
+            .flat_map(|tri| {
+                let corners = tri.map(|i| self.nodes[i].pos);
+                let center = corners.iter().sum::<Vec2>() / 3.0;
+                corners.map(|p| p.lerp(center, shrink))
+            })
+            .collect::<Vec<Vec2>>();
+        let indices = (0..vertices.len() as u32).collect();
+        (vertices, indices)
+    }
+
+    // the most links `get_indices` can list, every link of the flags torn or not and the
+    // stitches, so buffers sized for it hold the links whatever gets torn or mended
+    pub fn link_capacity(&self) -> usize {
+        self.flags.iter().map(|f| f.links.len()).sum::<usize>() + self.stitches.len()
+    }
+
+    pub fn num_links(&self) -> i32 {
+        self.alive_links().count() as i32
+    }
+
+    // distance every node moved during the last step
+    pub fn node_speeds(&self) -> Vec<f32> {
+        self.nodes
+            .iter()
+            .map(|n| n.pos.distance(n.last_pos))
+            .collect()
+    }
+
+    // for every link, in the same order as `get_indices`, whether an end is grabbed or pinned
+    pub fn link_highlights(&self) -> Vec<LinkHighlight> {
+        let mut selected = vec![false; self.nodes.len()];
+        for i in self.selected_nodes() {
+            selected[i] = true;
+        }
+        self.alive_links()
+            .map(|l| {
+                let ends = [l.node1, l.node2];
+                if ends.iter().any(|&i| selected[i]) {
+                    LinkHighlight::Selected
+                } else if ends.iter().any(|&i| self.nodes[i].pinned) {
+                    LinkHighlight::Pinned
+                } else {
+                    LinkHighlight::None
+                }
+            })
+            .collect()
+    }
+
+    // `(length - rest length) / rest length` of every link, in the same order as `get_indices`:
+    // flag by flag the links in the order they were built, structural then shear then bend,
+    // skipping torn ones, and the stitches last
+    pub fn link_strains(&self) -> Vec<f32> {
+        self.alive_links()
+            .map(|l| {
+                let dist = self.nodes[l.node1].pos.distance(self.nodes[l.node2].pos);
+                (dist - l.resting_distance) / l.resting_distance
+            })
+            .collect()
+    }
+
+    pub fn total_resting_length(&self) -> f32 {
+        self.alive_links().map(|l| l.resting_distance).sum()
+    }
+
+    pub fn total_current_length(&self) -> f32 {
+        self.alive_links()
+            .map(|l| self.nodes[l.node1].pos.distance(self.nodes[l.node2].pos))
+            .sum()
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // the width and height in nodes of each flag, whose nodes follow one another row by row
+    pub fn flags(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.flags.iter().map(|f| (f.width, f.height))
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.step_count
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // velocities are measured as the displacement over the last step, with unit mass
+    pub fn kinetic_energy(&self) -> f32 {
+        self.nodes
+            .iter()
+            .map(|n| (n.pos - n.last_pos).length_squared())
+            .sum()
+    }
+
+    // Rough headroom left before the solver becomes unstable at the pace of the last step: 0.0
+    // at the threshold and negative past it. Each flag is held against two limits and the margin
+    // is what the closest one leaves of 1.0: its stiffness, past 1.0 every solve adds energy (see
+    // `FlagParams::stiffness`), and how far its fastest node travelled during its last substep,
+    // its speed times the step's `dt` over its substeps, against its shortest link, which breaks
+    // the integration once reached. The stiffness alone caps the margin at 1.0 - stiffness, 0.5
+    // for the default one.
+    pub fn stability_margin(&self) -> f32 {
+        let usage = self.flags.iter().enumerate().map(|(f, flag)| {
+            let nodes = &self.nodes[flag.offset..flag.offset + flag.width * flag.height];
+            // positions are a substep apart, so this is the speed times the substep
+            let travel = nodes
+                .iter()
+                .map(|n| n.pos.distance(n.last_pos))
+                .fold(0.0, f32::max);
+            let min_rest = flag
+                .alive_links()
+                .map(|l| l.resting_distance)
+                .fold(f32::INFINITY, f32::min);
+            self.stiffness(f).max(travel / min_rest)
+        });
+        1.0 - usage.fold(0.0, f32::max)
+    }
+
+    // the statistics in one pass over the nodes and one over the links
+    pub fn stats(&self) -> SimStats {
+        let mut stats = SimStats {
+            min_speed: f32::INFINITY,
+            ..Default::default()
+        };
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        for n in &self.nodes {
+            let speed_squared = (n.pos - n.last_pos).length_squared();
+            stats.kinetic_energy += speed_squared;
+            stats.min_speed = stats.min_speed.min(speed_squared);
+            stats.max_speed = stats.max_speed.max(speed_squared);
+            if n.pos.x < min.x || n.pos.x > max.x || n.pos.y < min.y || n.pos.y > max.y {
+                stats.out_of_bounds += 1;
+            }
+        }
+        stats.min_speed = if self.nodes.is_empty() {
+            0.0
+        } else {
+            stats.min_speed.sqrt()
+        };
+        stats.max_speed = stats.max_speed.sqrt();
+
+        let (mut total_strain, mut num_links) = (0.0, 0);
+        for l in self.alive_links() {
+            let dist = self.nodes[l.node1].pos.distance(self.nodes[l.node2].pos);
+            total_strain += (dist - l.resting_distance) / l.resting_distance;
+            num_links += 1;
+        }
+        stats.mean_strain = total_strain / num_links.max(1) as f32;
+        stats.sub_steps = self.last_sub_steps;
+        stats
+    }
+
+    pub fn max_speed(&self) -> f32 {
+        self.nodes
+            .iter()
+            .map(|n| n.pos.distance(n.last_pos))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn select_nodes(&mut self, pos: Vec2) {
+        let radius = self.select_radius;
+        let in_range = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.absent)
+            .map(|(i, n)| (i, n.pos.distance_squared(pos)))
+            .filter(|(_, d)| *d < radius * radius)
+            .map(|(i, _)| (i, self.nodes[i].pos - pos))
+            .collect::<Vec<(usize, Vec2)>>();
+        self.grab_point = pos;
+        self.grab_falloff.clear();
+        if !in_range.is_empty() {
+            let falloff = self.grab_falloff_radius;
+            self.grab_falloff.extend(
+                self.nodes
+                    .iter()
+                    .enumerate()
+                    // pinned nodes only move when grabbed themselves
+                    .filter(|(i, n)| n.free() && !in_range.iter().any(|(j, _)| j == i))
+                    .map(|(i, n)| (i, 1.0 - n.pos.distance(pos) / falloff))
+                    .filter(|(_, t)| *t > 0.0)
+                    .map(|(i, t)| (i, t * t * (3.0 - 2.0 * t))),
+            );
+            self.selected_nodes = Some(in_range);
+            self.grab_start
+                .get_or_insert_with(Vec::new)
+                .clone_from(&self.nodes);
+        } else {
+            self.selected_nodes = None
+        }
+    }
+
+    // Puts the nodes back as they were when the last grab started, speed included, and drops the
+    // selection. Only the last grab is kept, and it is forgotten once undone or when flags
+    // are added, so this returns whether there was one to undo.
+    pub fn undo_grab(&mut self) -> bool {
+        match self.grab_start.take() {
+            Some(nodes) if nodes.len() == self.nodes.len() => {
+                self.restore_nodes(&nodes);
+                self.selected_nodes = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // nodes within `radius` of the grab point are selected, in world units
+    pub fn set_select_radius(&mut self, radius: f32) {
+        self.select_radius = radius;
+    }
+
+    // Grabbing also drags the nodes within `radius` of the grab point, less and less towards the
+    // edge, so it moves a region of cloth rather than pinching a single node. 0.0 disables it.
+    pub fn set_grab_falloff_radius(&mut self, radius: f32) {
+        self.grab_falloff_radius = radius;
+    }
+
+    pub fn set_max_grab_step(&mut self, max_step: f32) {
+        self.max_grab_step = max_step;
+    }
+
+    // the grabbed nodes keep their offset from the cursor, so a patch of cloth is dragged as is
+    pub fn move_selected_nodes(&mut self, pos: Vec2) {
+        let max_step = self.max_grab_step;
+        match &self.selected_nodes {
+            None => (),
+            Some(nodes) => nodes.iter().for_each(|&(i, offset)| {
+                let n = &mut self.nodes[i];
+                n.pos += (pos + offset - n.pos).clamp_length_max(max_step);
+            }),
+        }
+        let delta = (pos - self.grab_point).clamp_length_max(max_step);
+        self.grab_point += delta;
+        for &(i, weight) in &self.grab_falloff {
+            self.nodes[i].pos += delta * weight;
+        }
+        self.snapshot_pins();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a flag hanging from its top left corners, 100 wide
+    fn flag(width: usize, height: usize) -> FlagParams {
+        FlagParams::builder()
+            .grid(width, height)
+            .gravity(vec2(0.0, 200.0))
+            .build()
+            .unwrap()
+    }
+
+    // a file in the temporary directory, unique to the test process
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flags-{}-{}", std::process::id(), name))
+    }
+
+    fn node_at(pos: Vec2) -> Node {
+        Node {
+            pos,
+            last_pos: pos,
+            ..Node::default()
+        }
+    }
+
+    // where a node at `pos` ends up after one pass of the walls of a 100x50 world
+    fn constrained(pos: Vec2) -> Vec2 {
+        let mut nodes = [node_at(pos)];
+        apply_constraint(&mut nodes, WorldBounds::from_size(vec2(100.0, 50.0)));
+        nodes[0].pos
+    }
+
+    #[test]
+    fn left_wall_puts_node_inside() {
+        assert_eq!(constrained(vec2(-500.0, 20.0)), vec2(0.0, 20.0));
+    }
+
+    #[test]
+    fn right_wall_puts_node_inside() {
+        assert_eq!(constrained(vec2(600.0, 20.0)), vec2(100.0, 20.0));
+    }
+
+    #[test]
+    fn top_wall_puts_node_inside() {
+        assert_eq!(constrained(vec2(30.0, -300.0)), vec2(30.0, 0.0));
+    }
+
+    #[test]
+    fn bottom_wall_puts_node_inside() {
+        assert_eq!(constrained(vec2(30.0, 400.0)), vec2(30.0, 50.0));
+    }
+
+    #[test]
+    fn same_inputs_give_bit_identical_trajectories() {
+        let build = || {
+            let second = FlagParams {
+                corner: vec2(200.0, 0.0),
+                ..flag(6, 6)
+            };
+            let mut physics = Physics::new(&[flag(10, 8), second]);
+            physics.set_wind(vec2(80.0, 0.0), None);
+            physics
+        };
+        let (mut a, mut b) = (build(), build());
+        for _ in 0..200 {
+            a.step(1.0 / 600.0);
+            b.step(1.0 / 600.0);
+        }
+        let bits = |v: Vec2| v.to_array().map(f32::to_bits);
+        for (na, nb) in a.nodes().iter().zip(b.nodes()) {
+            assert_eq!(bits(na.pos), bits(nb.pos));
+            assert_eq!(bits(na.last_pos), bits(nb.last_pos));
+        }
+    }
+
+    #[test]
+    fn export_obj_writes_a_vertex_per_node() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(4, 3)
+        };
+        let physics = Physics::new(&[flag(5, 4), second]);
+        let path = temp_path("export.obj");
+        physics.export_obj(&path, 0.0).unwrap();
+        let obj = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let vertices = obj.lines().filter(|l| l.starts_with("v ")).count();
+        assert_eq!(vertices, physics.nodes().len());
+    }
+
+    #[test]
+    fn obj_round_trip_preserves_positions() {
+        let mut physics = Physics::new(&[flag(6, 4)]);
+        for _ in 0..50 {
+            physics.step(1.0 / 600.0);
+        }
+        let path = temp_path("round_trip.obj");
+        physics.export_obj(&path, 0.0).unwrap();
+        let loaded = Physics::from_obj(&path, 6, 4);
+        fs::remove_file(&path).unwrap();
+        let loaded_points = loaded.unwrap().get_points();
+        assert_eq!(loaded_points, physics.get_points());
+    }
+
+    #[test]
+    fn from_obj_rejects_degenerate_grids() {
+        let path = temp_path("empty.obj");
+        fs::write(&path, "").unwrap();
+        let results = [(0, 0), (1, 4), (4, 1)].map(|(w, h)| Physics::from_obj(&path, w, h));
+        fs::remove_file(&path).unwrap();
+        for result in results {
+            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn mask_gaps_are_absent_rather_than_pinned() {
+        // a 5x4 mask pinned at its top left pixel, with a transparent pixel at (2, 1)
+        let mask = image::RgbaImage::from_fn(5, 4, |x, y| match (x, y) {
+            (0, 0) => image::Rgba([255, 0, 0, 255]),
+            (2, 1) => image::Rgba([0, 0, 0, 0]),
+            _ => image::Rgba([255, 255, 255, 255]),
+        });
+        let path = temp_path("mask.png");
+        mask.save(&path).unwrap();
+        let physics = Physics::from_image_mask(&path, Vec2::ZERO, 100.0);
+        fs::remove_file(&path).unwrap();
+        let mut physics = physics.unwrap();
+
+        let hole = 2 + 5;
+        let start = physics.get_points()[hole];
+        assert!(physics.is_absent(hole) && !physics.is_pinned(hole));
+        assert_eq!((0..20).filter(|&i| physics.is_pinned(i)).count(), 1);
+        assert_ne!(physics.nearest_node(start), Some(hole));
+
+        physics.drive_pinned(|_, _| vec2(500.0, 500.0), 0.0);
+        physics.set_gravity(0, vec2(0.0, 200.0));
+        physics.enable_self_collision(true);
+        for _ in 0..100 {
+            physics.step(1.0 / 600.0);
+        }
+        assert_eq!(physics.get_points()[hole], start);
+
+        // the links to the gap are dead but not torn
+        assert_eq!(physics.dead_links().len(), 4);
+        assert_eq!(physics.num_torn_links(), 0);
+        physics.tear_link(0, 0);
+        assert_eq!(physics.num_torn_links(), 1);
+        physics.reset();
+        assert_eq!(physics.num_torn_links(), 0);
+        assert_eq!(physics.dead_links().len(), 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load_resume_the_same_simulation() {
+        // the second flag never tears, which json can only write as null
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(4, 4)
+        };
+        let mut physics = Physics::new(&[flag(8, 6), second]);
+        physics.set_tear_factor(0, 3.0);
+        for _ in 0..100 {
+            physics.step(1.0 / 600.0);
+        }
+        let path = temp_path("state.json");
+        physics.save(&path).unwrap();
+        let loaded = Physics::load(&path);
+        fs::remove_file(&path).unwrap();
+        let mut loaded = loaded.unwrap();
+        assert_eq!(loaded.get_points(), physics.get_points());
+        assert_eq!(loaded.step_count(), physics.step_count());
+
+        // the speed is kept too, so both carry on alike
+        for _ in 0..100 {
+            physics.step(1.0 / 600.0);
+            loaded.step(1.0 / 600.0);
+        }
+        assert_eq!(loaded.get_points(), physics.get_points());
+    }
+
+    #[test]
+    fn stability_margin_is_positive_when_stable_and_negative_when_blown_up() {
+        let dt = 1.0 / 60.0;
+        let mut stable = Physics::new(&[flag(10, 8)]);
+        for _ in 0..30 {
+            stable.step(dt);
+        }
+        let margin = stable.stability_margin();
+        assert!(margin > 0.0 && margin <= 0.5, "stable margin {}", margin);
+
+        // the default stiffness, but steps far too long for a single substep
+        let mut blown_up = Physics::new(&[flag(10, 8)]);
+        for _ in 0..30 {
+            blown_up.step(30.0 * dt);
+        }
+        let margin = blown_up.stability_margin();
+        assert!(margin < 0.0, "blown up margin {}", margin);
+    }
+
+    #[test]
+    fn stiffer_flags_have_less_stability_margin() {
+        let [soft, stiff] = [0.3, 0.8].map(|stiffness| {
+            let mut physics = Physics::new(&[FlagParams {
+                stiffness,
+                ..flag(10, 8)
+            }]);
+            for _ in 0..30 {
+                physics.step(1.0 / 60.0);
+            }
+            physics.stability_margin()
+        });
+        assert!(stiff < soft, "{} {}", soft, stiff);
+        assert!(stiff > 0.0);
+    }
+
+    // area of the cells of the flag, from the shoelace formula
+    fn cloth_area(physics: &Physics) -> f32 {
+        let points = physics.get_points();
+        physics
+            .quads(0)
+            .map(|quad| {
+                let corners = quad.map(|i| points[i]);
+                (0..4)
+                    .map(|k| corners[k].perp_dot(corners[(k + 1) % 4]))
+                    .sum::<f32>()
+                    .abs()
+                    * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn positive_pressure_inflates_the_flag() {
+        let still = FlagParams {
+            gravity: Vec2::ZERO,
+            pinning: Pinning::FourCorners,
+            ..flag(8, 8)
+        };
+        let area = cloth_area(&Physics::new(std::slice::from_ref(&still)));
+        let [flat, inflated] = [0.0, 2000.0].map(|pressure| {
+            let mut physics = Physics::new(std::slice::from_ref(&still));
+            physics.set_pressure(0, pressure);
+            for _ in 0..50 {
+                physics.step(1.0 / 600.0);
+            }
+            cloth_area(&physics)
+        });
+        assert_eq!(flat, area);
+        assert!(inflated > area * 1.005);
+    }
+
+    #[test]
+    fn pressure_moves_heavy_nodes_less() {
+        let still = FlagParams {
+            gravity: Vec2::ZERO,
+            pinning: Pinning::Custom(vec![]),
+            ..flag(4, 4)
+        };
+        let heavy = FlagParams {
+            mass: [10.0; 2],
+            ..still.clone()
+        };
+        let mut physics = [still, heavy].map(|params| Physics::new(&[params]));
+        let start = physics[0].get_points();
+        for p in &mut physics {
+            p.set_pressure(0, 2000.0);
+            p.step(1.0 / 600.0);
+        }
+        // the top left corner is pushed out of the cloth
+        let moved = physics.map(|p| p.get_points()[0].distance(start[0]));
+        assert!(moved[1] < moved[0] * 0.5, "{:?}", moved);
+    }
+
+    #[test]
+    fn links_changed_is_raised_by_tears_and_stitches_and_taken_once() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(4, 4)
+        };
+        let mut physics = Physics::new(&[flag(4, 4), second]);
+        assert!(!physics.take_links_changed());
+        let capacity = physics.link_capacity();
+
+        physics.tear_link(0, 0);
+        assert!(physics.take_links_changed());
+        assert!(!physics.take_links_changed());
+        assert_eq!(physics.link_capacity(), capacity);
+        assert!(physics.get_indices().len() < capacity * 2);
+
+        physics.stitch(0, Edge::Right, 1, Edge::Left).unwrap();
+        assert!(physics.take_links_changed());
+        assert_eq!(physics.link_capacity(), capacity + 4);
+    }
+
+    #[test]
+    fn pin_path_moves_the_pinned_group_along_the_path() {
+        let mut physics = Physics::new(&[flag(6, 4)]);
+        let points = physics.get_points();
+        let pinned = (0..points.len())
+            .filter(|&i| physics.is_pinned(i))
+            .collect::<Vec<_>>();
+        let center = pinned.iter().map(|&i| points[i]).sum::<Vec2>() / pinned.len() as f32;
+        // two legs of 100, so halfway through the group sits on the corner
+        let path = vec![
+            center,
+            center + vec2(100.0, 0.0),
+            center + vec2(100.0, 100.0),
+        ];
+        physics.set_pin_path(0, path, 1.0);
+        for _ in 0..50 {
+            physics.step(0.01);
+        }
+        let moved = physics.get_points();
+        for &i in &pinned {
+            let expected = points[i] + vec2(100.0, 0.0);
+            assert!(moved[i].distance(expected) < 0.5, "{} at {}", i, moved[i]);
+        }
+    }
+
+    #[test]
+    fn velocity_clamp_falls_faster_than_total_clamp() {
+        let falling = FlagParams {
+            gravity: vec2(0.0, 100_000.0),
+            pinning: Pinning::Custom(vec![]),
+            ..flag(3, 3)
+        };
+        let [total, velocity] = [ClampMode::Total, ClampMode::Velocity].map(|mode| {
+            let mut physics = Physics::new(std::slice::from_ref(&falling));
+            physics.set_clamp_mode(mode);
+            physics.set_damping(0, 0.0);
+            for _ in 0..100 {
+                physics.step(0.01);
+            }
+            let n = &physics.nodes()[0];
+            n.pos.y - n.last_pos.y
+        });
+        // gravity gives 10 a step, which only the velocity clamp adds past the limit
+        assert!((total - 50.0).abs() < 1e-3, "total {}", total);
+        assert!((velocity - 60.0).abs() < 1e-3, "velocity {}", velocity);
+    }
+
+    #[test]
+    fn quads_cover_every_cell_with_its_corners_in_order() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(5, 3)
+        };
+        let physics = Physics::new(&[flag(4, 4), second]);
+        let points = physics.get_points();
+        let quads = physics.quads(1).collect::<Vec<_>>();
+        assert_eq!(quads.len(), (5 - 1) * (3 - 1));
+        assert_eq!(quads[0], [16, 17, 22, 21]);
+        for [a, b, c, d] in quads {
+            // the nodes are laid out on a grid, so every cell is an axis aligned rectangle
+            let across = points[b] - points[a];
+            let down = points[d] - points[a];
+            assert!(across.x > 0.0 && across.y == 0.0);
+            assert!(down.x == 0.0 && down.y > 0.0);
+            assert_eq!(points[c], points[a] + across + down);
+        }
+    }
+
+    #[test]
+    fn interaction_solve_only_runs_extra_iterations_near_the_grab() {
+        let still = FlagParams {
+            gravity: Vec2::ZERO,
+            ..flag(10, 10)
+        };
+        let [base, extra] = [0, 20].map(|iterations| {
+            let mut physics = Physics::new(std::slice::from_ref(&still));
+            physics.set_interaction_solve(iterations, 15.0);
+            let grabbed = physics.get_points()[55];
+            physics.set_select_radius(1.0);
+            physics.select_nodes(grabbed);
+            physics.move_selected_nodes(grabbed + vec2(30.0, 0.0));
+            physics.step(1.0 / 60.0);
+            physics.link_strains()
+        });
+        let indices = Physics::new(std::slice::from_ref(&still)).get_indices();
+        let points = Physics::new(std::slice::from_ref(&still)).get_points();
+        let spacing = points[0].distance(points[1]);
+        for (i, pair) in indices.chunks(2).enumerate() {
+            let distance = pair
+                .iter()
+                .map(|&n| points[n as usize].distance(points[55]))
+                .fold(f32::INFINITY, f32::min);
+            if distance > 15.0 + 2.0 * spacing {
+                // out of reach of the extra iterations, solved as many times as before
+                assert_eq!(base[i], extra[i], "link {}", i);
+            }
+        }
+        let near = |strains: &[f32]| {
+            indices
+                .chunks(2)
+                .zip(strains)
+                .filter(|(pair, _)| pair.contains(&55))
+                .map(|(_, s)| s.abs())
+                .fold(0.0, f32::max)
+        };
+        assert!(near(&extra) < near(&base) * 0.75);
+    }
+
+    #[test]
+    fn pin_edge_span_pins_only_the_span() {
+        let loose = FlagParams {
+            pinning: Pinning::Custom(vec![]),
+            ..flag(4, 4)
+        };
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..loose.clone()
+        };
+        let mut physics = Physics::new(&[loose, second]);
+        physics.pin_edge_span(1, Edge::Right, 1..3);
+        // past the end of the edge is ignored
+        physics.pin_edge_span(1, Edge::Bottom, 3..10);
+        let pinned = (0..physics.num_nodes())
+            .filter(|&i| physics.is_pinned(i))
+            .collect::<Vec<_>>();
+        assert_eq!(pinned, [16 + 7, 16 + 11, 16 + 15]);
+    }
+
+    #[test]
+    fn cached_link_length_is_measured_during_the_step() {
+        let mut physics = Physics::new(&[flag(5, 5)]);
+        physics.set_link_length_cache(true);
+        // without stiffness the links measure the nodes where gravity left them
+        physics.set_stiffness(0, 0.0);
+        let start = physics.get_points();
+        assert_eq!(physics.get_indices()[..2], [0, 1]);
+        physics.step(0.1);
+        // node 0 is pinned and node 1 falls by gravity * dt^2
+        let spacing = start[0].distance(start[1]);
+        let expected = (spacing * spacing + 2.0 * 2.0).sqrt();
+        assert!((physics.link_length(0, 0) - expected).abs() < 1e-4);
+        let points = physics.get_points();
+        assert_eq!(physics.link_length(0, 0), points[0].distance(points[1]));
+    }
+
+    #[test]
+    fn disabling_shear_lets_the_cells_shear_while_structural_links_hold() {
+        let sideways = FlagParams {
+            gravity: vec2(100.0, 200.0),
+            pinning: Pinning::Custom(vec![]),
+            shear: true,
+            ..flag(5, 5)
+        };
+        let [sheared, square] = [false, true].map(|shear| {
+            let mut physics = Physics::new(std::slice::from_ref(&sideways));
+            physics.pin_edge_span(0, Edge::Top, 0..5);
+            physics.set_constraint_enabled(ConstraintKind::Shear, shear);
+            for _ in 0..300 {
+                physics.step(1.0 / 60.0);
+            }
+            physics
+        });
+        // cosine of the angle of the top left cell, 0 while it stays square
+        let skew = |physics: &Physics| {
+            let points = physics.get_points();
+            let across = (points[1] - points[0]).normalize();
+            let down = (points[5] - points[0]).normalize();
+            across.dot(down).abs()
+        };
+        let structural_strain = |physics: &Physics| {
+            physics.flags[0]
+                .offset_links
+                .iter()
+                .filter(|l| l.kind == ConstraintKind::Structural)
+                .map(|l| {
+                    let dist = physics.nodes[l.node1]
+                        .pos
+                        .distance(physics.nodes[l.node2].pos);
+                    ((dist - l.resting_distance) / l.resting_distance).abs()
+                })
+                .fold(0.0, f32::max)
+        };
+        assert!(skew(&sheared) > 0.5);
+        assert!(skew(&square) < 0.2);
+        assert!(structural_strain(&sheared) < 0.05);
+    }
+
+    #[test]
+    fn length_ratio_follows_a_uniform_stretch() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            shear: true,
+            ..flag(6, 4)
+        };
+        let mut physics = Physics::new(&[flag(5, 5), second]);
+        let ratio = |p: &Physics| p.total_current_length() / p.total_resting_length();
+        assert!((ratio(&physics) - 1.0).abs() < 1e-5);
+
+        let stretched = physics
+            .nodes()
+            .iter()
+            .map(|n| node_at(n.pos * 1.1))
+            .collect::<Vec<_>>();
+        physics.restore_nodes(&stretched);
+        assert!((ratio(&physics) - 1.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn overlapping_wind_fields_add_up() {
+        let fields = [
+            WindField {
+                force: vec2(100.0, 0.0),
+                falloff: Falloff::Uniform,
+            },
+            WindField {
+                force: vec2(0.0, -400.0),
+                falloff: Falloff::Radial {
+                    center: vec2(0.0, 0.0),
+                    radius: 20.0,
+                },
+            },
+        ];
+        let mut physics = Physics::new(&[]);
+        let ids = fields.map(|f| physics.add_wind_field(f));
+        // halfway out of the radial field, so it pushes at half strength
+        let mut nodes = [node_at(vec2(10.0, 0.0))];
+        apply_wind(&mut nodes, &physics.wind_fields, 0.5);
+        assert_eq!(nodes[0].pos, vec2(10.0, 0.0) + vec2(100.0, -200.0) * 0.25);
+
+        physics.remove_wind_field(ids[0]);
+        let mut nodes = [node_at(vec2(10.0, 0.0))];
+        apply_wind(&mut nodes, &physics.wind_fields, 0.5);
+        assert_eq!(nodes[0].pos, vec2(10.0, 0.0) + vec2(0.0, -200.0) * 0.25);
+    }
+
+    #[test]
+    fn exploded_triangles_shrink_towards_their_centroids() {
+        let physics = Physics::new(&[flag(4, 3)]);
+        let num_quads = physics.quads(0).count();
+        let (whole, _) = physics.get_exploded_triangles(0.0);
+        let (vertices, indices) = physics.get_exploded_triangles(0.25);
+        assert_eq!(vertices.len(), num_quads * 6);
+        assert_eq!(indices, (0..vertices.len() as u32).collect::<Vec<_>>());
+        for (tri, shrunk) in whole.chunks(3).zip(vertices.chunks(3)) {
+            let center = tri.iter().sum::<Vec2>() / 3.0;
+            for (p, q) in tri.iter().zip(shrunk) {
+                // a quarter of the way to the centroid
+                assert!(q.distance(*p + (center - *p) * 0.25) < 1e-4);
+                assert!(q.distance(center) < p.distance(center));
+            }
+        }
+        // without shrinking the corners are the nodes, two triangles a quad
+        let points = physics.get_points();
+        let [a, b, c, d] = physics.quads(0).next().unwrap().map(|i| points[i]);
+        assert_eq!(whole[..6], [a, b, c, a, c, d]);
+    }
+
+    #[test]
+    fn grabbed_nodes_move_at_most_max_grab_step_a_call() {
+        let mut physics = Physics::new(&[flag(5, 5)]);
+        physics.set_max_grab_step(5.0);
+        physics.set_select_radius(1.0);
+        let start = physics.get_points()[12];
+        physics.select_nodes(start);
+        assert_eq!(physics.selected_nodes().collect::<Vec<_>>(), [12]);
+
+        let target = start + vec2(30.0, 40.0);
+        physics.move_selected_nodes(target);
+        let moved = physics.get_points()[12];
+        assert!((moved.distance(start) - 5.0).abs() < 1e-4);
+        // straight towards the cursor
+        assert!(moved.distance(start + vec2(3.0, 4.0)) < 1e-4);
+
+        // and it catches up over the next calls
+        for _ in 0..9 {
+            physics.move_selected_nodes(target);
+        }
+        assert!(physics.get_points()[12].distance(target) < 1e-4);
+    }
+
+    #[test]
+    fn more_substeps_keep_a_flag_together_under_a_long_step() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(8, 8)
+        };
+        let mut physics = Physics::new(&[flag(8, 8), second]);
+        physics.set_substeps(1, 8);
+        for _ in 0..200 {
+            physics.step(0.1);
+        }
+        let strains = physics.link_strains();
+        let (first, second) = strains.split_at(physics.flags[0].links.len());
+        let max_strain = |s: &[f32]| s.iter().copied().fold(0.0, f32::max);
+        // a single substep lets gravity stretch the flag to several times its size
+        assert!(max_strain(first) > 1.0, "{}", max_strain(first));
+        assert!(max_strain(second) < 0.2, "{}", max_strain(second));
+    }
+
+    #[test]
+    fn torn_links_move_from_the_indices_to_dead_links() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(4, 4)
+        };
+        let mut physics = Physics::new(&[flag(4, 4), second]);
+        assert!(physics.dead_links().is_empty());
+        let pairs = |p: &Physics| {
+            p.get_indices()
+                .chunks(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect::<Vec<_>>()
+        };
+        let before = pairs(&physics);
+
+        let l = physics.flags[1].offset_links[3];
+        physics.tear_link(1, 3);
+        assert_eq!(physics.dead_links(), [LinkId { flag: 1, link: 3 }]);
+        let after = pairs(&physics);
+        assert_eq!(after.len(), before.len() - 1);
+        assert!(!after.contains(&(l.node1 as u32, l.node2 as u32)));
+        // the others keep their order
+        assert!(before
+            .iter()
+            .filter(|&&p| p != (l.node1 as u32, l.node2 as u32))
+            .eq(after.iter()));
+    }
+
+    #[test]
+    fn pinned_nodes_stay_bit_identical_through_the_step() {
+        let second = FlagParams {
+            corner: vec2(120.0, 0.0),
+            shear: true,
+            pinning: Pinning::FourCorners,
+            ..flag(8, 8)
+        };
+        let mut physics = Physics::new(&[flag(8, 8), second]);
+        physics.set_pin_check(true);
+        physics.set_wind(vec2(300.0, 0.0), None);
+        physics.set_pressure(1, 500.0);
+        physics.set_drag(1, 0.5);
+        physics.enable_self_collision(true);
+        physics.add_obstacle(Obstacle {
+            center: vec2(50.0, 80.0),
+            radius: 20.0,
+        });
+        physics.stitch(0, Edge::Right, 1, Edge::Left).unwrap();
+        let start = physics.get_points();
+        let pinned = (0..physics.num_nodes())
+            .filter(|&i| physics.is_pinned(i))
+            .collect::<Vec<_>>();
+        assert_eq!(pinned.len(), 6);
+        for _ in 0..300 {
+            physics.step(1.0 / 60.0);
+        }
+        let points = physics.get_points();
+        for &i in &pinned {
+            assert_eq!(points[i].to_array(), start[i].to_array(), "node {}", i);
+        }
+        // nothing for the check to report either
+        let snapshot = physics.pin_check.as_ref().unwrap();
+        assert!(pinned.iter().all(|&i| snapshot[i] == start[i]));
+    }
+
+    #[test]
+    fn boundary_margin_keeps_nodes_off_the_window_edge() {
+        let falling = FlagParams {
+            pinning: Pinning::Custom(vec![]),
+            ..flag(5, 5)
+        };
+        let mut physics = Physics::new(&[falling]);
+        physics.set_bounds(WorldBounds::from_size(vec2(400.0, 300.0)));
+        physics.set_boundary_margin(15.0);
+        for _ in 0..300 {
+            physics.step(1.0 / 60.0);
+        }
+        // the flag lies on the floor, short of the bottom edge by the margin
+        let lowest = physics
+            .get_points()
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert!((lowest - 285.0).abs() < 1e-3, "{}", lowest);
+    }
+
+    #[test]
+    fn merge_adds_up_the_counts_and_offsets_the_links() {
+        let mut physics = Physics::new(&[flag(4, 4)]);
+        let right = FlagParams {
+            corner: vec2(300.0, 0.0),
+            ..flag(3, 5)
+        };
+        let mut other = Physics::new(&[
+            FlagParams {
+                corner: vec2(150.0, 0.0),
+                ..flag(5, 3)
+            },
+            right,
+        ]);
+        other.stitch(0, Edge::Bottom, 1, Edge::Right).unwrap();
+        let (num_nodes, num_links) = (physics.num_nodes(), physics.num_links());
+        let capacity = physics.link_capacity();
+        let expected_points = [physics.get_points(), other.get_points()].concat();
+        let other_links = other.num_links();
+        let other_capacity = other.link_capacity();
+        let other_nodes = other.num_nodes();
+
+        physics.merge(other);
+        assert_eq!(physics.num_nodes(), num_nodes + other_nodes);
+        assert_eq!(physics.num_links(), num_links + other_links);
+        assert_eq!(physics.link_capacity(), capacity + other_capacity);
+        assert_eq!(physics.get_points(), expected_points);
+        for f in &physics.flags {
+            let nodes = f.offset..(f.offset + f.width * f.height);
+            for l in &f.offset_links {
+                assert!(nodes.contains(&l.node1) && nodes.contains(&l.node2));
+                // every flag is at rest, so the links still join nodes their length apart
+                let length = expected_points[l.node1].distance(expected_points[l.node2]);
+                assert!((length - l.resting_distance).abs() < 1e-3);
+            }
+        }
+        let [a, b] = [1, 2].map(|f| {
+            let f = &physics.flags[f];
+            f.offset..(f.offset + f.width * f.height)
+        });
+        for s in &physics.stitches {
+            assert_eq!(s.flags, [1, 2]);
+            assert!(a.contains(&s.link.node1) && b.contains(&s.link.node2));
+        }
+    }
+
+    #[test]
+    fn startup_ramp_reaches_the_target_stiffness_after_its_steps() {
+        let mut physics = Physics::new(&[flag(4, 4)]);
+        let target = physics.stiffness(0);
+        physics.set_startup_ramp_steps(10);
+        assert_eq!(physics.stiffness(0), 0.0);
+        let mut last = 0.0;
+        for _ in 0..9 {
+            physics.step(1.0 / 60.0);
+            let stiffness = physics.stiffness(0);
+            assert!(stiffness > last && stiffness < target);
+            last = stiffness;
+        }
+        physics.step(1.0 / 60.0);
+        assert_eq!(physics.stiffness(0), target);
+        physics.step(1.0 / 60.0);
+        assert_eq!(physics.stiffness(0), target);
+    }
+
+    #[test]
+    fn a_damped_node_slows_down_faster_than_its_neighbours() {
+        let drifting = FlagParams {
+            gravity: Vec2::ZERO,
+            pinning: Pinning::Custom(vec![]),
+            ..flag(5, 5)
+        };
+        let mut physics = Physics::new(&[drifting]);
+        physics.set_damping(0, 0.0);
+        let moving = physics
+            .nodes()
+            .iter()
+            .map(|n| Node {
+                last_pos: n.pos - vec2(2.0, 0.0),
+                ..*n
+            })
+            .collect::<Vec<_>>();
+        physics.restore_nodes(&moving);
+        physics.set_node_damping(12, 0.5);
+        physics.step(1.0 / 60.0);
+        let speeds = physics.node_speeds();
+        for neighbour in [7, 11, 13, 17] {
+            assert!(speeds[12] < speeds[neighbour] * 0.9, "{:?}", speeds);
+        }
+        // away from it the flag keeps drifting as a whole
+        assert!((speeds[0] - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn grab_falloff_moves_the_center_more_than_the_edge_and_not_the_pins() {
+        let mut physics = Physics::new(&[flag(11, 11)]);
+        let start = physics.get_points();
+        // 10 apart, the falloff reaches the pinned corner 0 from node 2
+        physics.set_select_radius(1.0);
+        physics.set_grab_falloff_radius(35.0);
+        physics.select_nodes(start[2]);
+        physics.move_selected_nodes(start[2] + vec2(0.0, 10.0));
+        let moved = physics
+            .get_points()
+            .iter()
+            .zip(&start)
+            .map(|(p, s)| p.distance(*s))
+            .collect::<Vec<_>>();
+        assert_eq!(moved[2], 10.0);
+        assert!(moved[3] > moved[4] && moved[4] > moved[5] && moved[5] > 0.0);
+        assert!((moved[1] - moved[3]).abs() < 1e-4);
+        assert!(physics.is_pinned(0));
+        assert_eq!(moved[0], 0.0);
+    }
+
+    #[test]
+    fn kinematic_node_follows_its_target_exactly() {
+        let mut physics = Physics::new(&[flag(6, 6)]);
+        let target = |t: f32| {
+            vec2(
+                50.0 + 30.0 * (4.0 * t).cos(),
+                120.0 + 20.0 * (4.0 * t).sin(),
+            )
+        };
+        physics.set_kinematic(20, target);
+        for _ in 0..120 {
+            physics.step(1.0 / 60.0);
+            assert_eq!(physics.get_points()[20], target(physics.time()));
+        }
+    }
+
+    #[test]
+    fn nearest_link_measures_the_perpendicular_distance() {
+        let second = FlagParams {
+            corner: vec2(200.0, 0.0),
+            ..flag(3, 3)
+        };
+        let physics = Physics::new(&[flag(3, 3), second]);
+        let points = physics.get_points();
+        // the top link between the first two nodes of the second flag
+        let (a, b) = (points[9], points[10]);
+        let link = physics.flags[1]
+            .offset_links
+            .iter()
+            .position(|l| (l.node1, l.node2) == (9, 10))
+            .unwrap();
+        // just above its middle, outside the flag
+        let off = (a + b) / 2.0 + (b - a).perp().normalize() * -3.0;
+        let (flag, nearest, distance) = physics.nearest_link(off).unwrap();
+        assert_eq!((flag, nearest), (1, link));
+        assert!((distance - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn metrics_sink_sees_every_step_once_in_order() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut physics = Physics::new(&[flag(4, 4)]);
+        let sink = seen.clone();
+        physics.set_metrics_sink(move |m| sink.lock().unwrap().push((m.step_count, m.time)));
+        physics.set_sub_steps(3);
+        for _ in 0..4 {
+            physics.advance(0.1);
+        }
+        physics.step(0.1);
+        let seen = seen.lock().unwrap();
+        let counts = seen.iter().map(|&(count, _)| count).collect::<Vec<_>>();
+        assert_eq!(counts, (1..=13).collect::<Vec<_>>());
+        assert!(seen.windows(2).all(|w| w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn a_cambered_flag_settles_into_a_curve() {
+        let floating = FlagParams {
+            gravity: Vec2::ZERO,
+            pinning: Pinning::Custom(vec![]),
+            shear: true,
+            ..flag(7, 7)
+        };
+        let [flat, cambered] = [0.0, 0.6].map(|camber| {
+            let mut physics = Physics::new(std::slice::from_ref(&floating));
+            physics.set_camber(0, camber);
+            for _ in 0..600 {
+                physics.step(1.0 / 60.0);
+            }
+            physics.get_points()
+        });
+        // how far the middle of the top row strays from the line through its ends
+        let bow = |points: &[Vec2]| {
+            let (a, b) = (points[0], points[6]);
+            (points[3] - a).perp_dot((b - a).normalize()).abs()
+        };
+        let width = |points: &[Vec2], row: usize| points[row * 7].distance(points[row * 7 + 6]);
+        assert!(bow(&flat) < 1e-3);
+        assert!(bow(&cambered) > 2.0, "{}", bow(&cambered));
+        assert!(width(&cambered, 6) > width(&cambered, 0) * 1.3);
+    }
+
+    #[test]
+    fn indices_of_a_large_flag_stay_within_the_nodes() {
+        let large = FlagParams {
+            shear: true,
+            bend: true,
+            ..flag(200, 200)
+        };
+        let physics = Physics::new(&[flag(4, 4), large]);
+        let num_nodes = physics.num_nodes();
+        assert!(num_nodes > i16::MAX as usize);
+        let indices = physics.get_indices();
+        assert!(indices.iter().all(|&i| (i as usize) < num_nodes));
+        // the last nodes are reached, which an i16 would have wrapped
+        assert_eq!(indices.iter().max(), Some(&(num_nodes as u32 - 1)));
+        let triangles = physics.get_triangle_indices();
+        assert!(triangles.iter().all(|&i| (i as usize) < num_nodes));
+    }
+
+    #[test]
+    fn kinetic_energy_decays_towards_rest() {
+        let mut physics = Physics::new(&[flag(8, 8)]);
+        physics.set_damping(0, 0.02);
+        // throw the free nodes sideways and up
+        let kicked = physics
+            .nodes()
+            .iter()
+            .map(|n| Node {
+                last_pos: if n.pinned {
+                    n.pos
+                } else {
+                    n.pos - vec2(3.0, -1.0)
+                },
+                ..*n
+            })
+            .collect::<Vec<_>>();
+        physics.restore_nodes(&kicked);
+        let start = physics.kinetic_energy();
+        // the mean energy over every second of the swing
+        let seconds = (0..10)
+            .map(|_| {
+                let mut energy = 0.0;
+                for _ in 0..60 {
+                    physics.step(1.0 / 60.0);
+                    energy += physics.kinetic_energy();
+                }
+                energy / 60.0
+            })
+            .collect::<Vec<f32>>();
+        assert!(seconds[0] < start);
+        assert!(seconds.windows(2).all(|w| w[1] < w[0]), "{:?}", seconds);
+        assert!(seconds[9] < start * 1e-4);
+    }
+
+    #[test]
+    fn degenerate_flag_params_are_rejected() {
+        let degenerate = [
+            FlagParams {
+                width: 1,
+                ..flag(4, 4)
+            },
+            FlagParams {
+                height: 0,
+                ..flag(4, 4)
+            },
+            FlagParams {
+                size: 0.0,
+                ..flag(4, 4)
+            },
+            FlagParams {
+                size: f32::NAN,
+                ..flag(4, 4)
+            },
+            FlagParams {
+                mass: [1.0, -1.0],
+                ..flag(4, 4)
+            },
+            FlagParams {
+                mass: [f32::NAN, 1.0],
+                ..flag(4, 4)
+            },
+            FlagParams {
+                pinning: Pinning::Custom(vec![0, 16]),
+                ..flag(4, 4)
+            },
+        ];
+        for params in degenerate {
+            assert!(params.validate().is_err());
+            let error = Physics::try_new(&[flag(3, 3), params]).err().unwrap();
+            assert!(error.starts_with("flag 1: "), "{}", error);
         }
+        assert!(FlagParams::builder().grid(1, 5).build().is_err());
+        assert!(FlagParams::builder().grid(2, 2).size(-3.0).build().is_err());
+        assert!(Physics::try_new(&[flag(2, 2)]).is_ok());
     }
 }
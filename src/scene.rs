@@ -0,0 +1,88 @@
+use std::fs;
+
+use flags::physics::{FlagParams, Physics, Pinning};
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::{DAMPING, GRAVITY, MAX_PUSH, STIFFNESS};
+
+// A scene loaded with `--config`, for example:
+//
+//     gravity = [0.0, 300.0]
+//     wind = [150.0, 0.0]
+//     sub_steps = 12
+//
+//     [[flag]]
+//     corner = [100.0, 100.0]
+//     size = 600.0
+//     grid = [40, 25]
+//     stiffness = 0.4
+//     pinning = "TopEdge"
+//
+// `pinning` is one of TopLeftCorners, the default, TopEdge, FourCorners, LeftEdge, or
+// `{ Custom = [0, 39] }` listing node indices row by row.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneConfig {
+    gravity: Option<[f32; 2]>,
+    wind: Option<[f32; 2]>,
+    sub_steps: Option<usize>,
+    #[serde(rename = "flag")]
+    flags: Vec<FlagConfig>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlagConfig {
+    corner: [f32; 2],
+    size: f32,
+    grid: [usize; 2],
+    stiffness: Option<f32>,
+    damping: Option<f32>,
+    #[serde(default)]
+    pinning: Pinning,
+}
+
+impl SceneConfig {
+    pub fn load(path: &str) -> Result<SceneConfig, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("could not read `{}`: {}", path, e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid scene in `{}`: {}", path, e))
+    }
+
+    pub fn build(&self) -> Result<Physics, String> {
+        if self.flags.is_empty() {
+            return Err("the scene has no [[flag]]".to_string());
+        }
+        let gravity = self.gravity.map_or(GRAVITY, Vec2::from);
+        let flags = self
+            .flags
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                FlagParams::builder()
+                    .corner(f.corner.into())
+                    .size(f.size)
+                    .grid(f.grid[0], f.grid[1])
+                    .shear(true)
+                    .bend(false)
+                    .gravity(gravity)
+                    .stiffness(f.stiffness.unwrap_or(STIFFNESS))
+                    .max_push(MAX_PUSH)
+                    .damping(f.damping.unwrap_or(DAMPING))
+                    .pinning(f.pinning.clone())
+                    .build()
+                    .map_err(|e| format!("flag {}: {}", i, e))
+            })
+            .collect::<Result<Vec<FlagParams>, String>>()?;
+
+        let mut physics = Physics::new(&flags);
+        if let Some(wind) = self.wind {
+            physics.set_wind(wind.into(), None);
+        }
+        if let Some(sub_steps) = self.sub_steps {
+            physics.set_sub_steps(sub_steps);
+        }
+        Ok(physics)
+    }
+}
@@ -2,6 +2,7 @@ use miniquad::*;
 
 pub const VERTEX: &str = r#"#version 100
   attribute vec2 pos;
+  attribute vec4 color0;
 
   varying lowp vec4 color;
 
@@ -10,7 +11,7 @@ pub const VERTEX: &str = r#"#version 100
   void main() {
       vec4 pos = vec4(pos, 0.0, 1.0);
       gl_Position = mvp * pos;
-      color = vec4(0.5, 0.8, 1.0, 1.0);
+      color = color0;
   }
   "#;
 
@@ -35,3 +36,158 @@ pub fn meta() -> ShaderMeta {
 pub struct Uniforms {
     pub mvp: glam::Mat4,
 }
+
+// the lines of the 3D view, all of one color, with the fragment shader of the 2D lines
+pub const VERTEX_3D: &str = r#"#version 100
+  attribute vec3 pos;
+
+  varying lowp vec4 color;
+
+  uniform mat4 mvp;
+  uniform vec4 line_color;
+
+  void main() {
+      gl_Position = mvp * vec4(pos, 1.0);
+      color = line_color;
+  }
+  "#;
+
+pub fn meta_3d() -> ShaderMeta {
+    ShaderMeta {
+        images: vec![],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("line_color", UniformType::Float4),
+            ],
+        },
+    }
+}
+
+#[repr(C)]
+pub struct Uniforms3d {
+    pub mvp: glam::Mat4,
+    pub line_color: glam::Vec4,
+}
+
+pub const TEXT_VERTEX: &str = r#"#version 100
+  attribute vec2 pos;
+
+  uniform mat4 mvp;
+
+  void main() {
+      gl_Position = mvp * vec4(pos, 0.0, 1.0);
+  }
+  "#;
+
+pub const TEXT_FRAGMENT: &str = r#"#version 100
+  uniform lowp vec4 color;
+
+  void main() {
+      gl_FragColor = color;
+  }
+  "#;
+
+pub fn text_meta() -> ShaderMeta {
+    ShaderMeta {
+        images: vec![],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("color", UniformType::Float4),
+            ],
+        },
+    }
+}
+
+#[repr(C)]
+pub struct TextUniforms {
+    pub mvp: glam::Mat4,
+    pub color: glam::Vec4,
+}
+
+pub const FILLED_VERTEX: &str = TEXT_VERTEX;
+
+// the rest shape is wound clockwise on screen, which GL considers back facing, so
+// `gl_FrontFacing` is set on the parts of the cloth folded over
+pub const FILLED_FRAGMENT: &str = r#"#version 100
+  uniform lowp vec4 front_color;
+  uniform lowp vec4 back_color;
+
+  void main() {
+      gl_FragColor = gl_FrontFacing ? back_color : front_color;
+  }
+  "#;
+
+pub fn filled_meta() -> ShaderMeta {
+    ShaderMeta {
+        images: vec![],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("mvp", UniformType::Mat4),
+                UniformDesc::new("front_color", UniformType::Float4),
+                UniformDesc::new("back_color", UniformType::Float4),
+            ],
+        },
+    }
+}
+
+#[repr(C)]
+pub struct FilledUniforms {
+    pub mvp: glam::Mat4,
+    pub front_color: glam::Vec4,
+    pub back_color: glam::Vec4,
+}
+
+pub const TEXTURED_VERTEX: &str = r#"#version 100
+  attribute vec2 pos;
+  attribute vec2 uv;
+
+  varying mediump vec2 texcoord;
+
+  uniform mat4 mvp;
+
+  void main() {
+      gl_Position = mvp * vec4(pos, 0.0, 1.0);
+      texcoord = uv;
+  }
+  "#;
+
+pub const TEXTURED_FRAGMENT: &str = r#"#version 100
+  varying mediump vec2 texcoord;
+
+  uniform sampler2D tex;
+
+  void main() {
+      gl_FragColor = texture2D(tex, texcoord);
+  }
+  "#;
+
+pub fn textured_meta() -> ShaderMeta {
+    ShaderMeta {
+        images: vec!["tex".to_string()],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
+        },
+    }
+}
+
+// builds the `name` shader, a compile error carrying the log of the GLSL compiler
+pub fn compile(
+    ctx: &mut Context,
+    name: &str,
+    vertex: &str,
+    fragment: &str,
+    meta: ShaderMeta,
+) -> Result<Shader, String> {
+    Shader::new(ctx, vertex, fragment, meta).map_err(|e| match e {
+        ShaderError::CompilationError {
+            shader_type,
+            error_message,
+        } => format!(
+            "the {:?} stage of the {} shader does not compile:\n{}",
+            shader_type, name, error_message
+        ),
+        e => format!("the {} shader could not be built: {}", name, e),
+    })
+}
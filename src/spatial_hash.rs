@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+// Buckets points in a uniform grid of `cell` sized squares, so the points near a position are
+// found in the 3x3 cells around it rather than among all of them.
+pub struct SpatialHash {
+    cell: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell: f32) -> SpatialHash {
+        SpatialHash {
+            cell,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell
+    }
+
+    fn key(&self, pos: Vec2) -> (i32, i32) {
+        let k = (pos / self.cell).floor();
+        (k.x as i32, k.y as i32)
+    }
+
+    // replaces the content with `points`, indexed by their position in the iterator
+    pub fn rebuild(&mut self, points: impl Iterator<Item = Vec2>) {
+        // buckets the previous points left empty are dropped, the others keep their allocation
+        self.cells.retain(|_, bucket| !bucket.is_empty());
+        self.cells.values_mut().for_each(|bucket| bucket.clear());
+        for (i, pos) in points.enumerate() {
+            let key = self.key(pos);
+            self.cells.entry(key).or_default().push(i);
+        }
+    }
+
+    // calls `f` with every point in the cells around `pos`, which includes all the points closer
+    // than the cell size
+    pub fn for_each_near(&self, pos: Vec2, mut f: impl FnMut(usize)) {
+        let (x, y) = self.key(pos);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if let Some(bucket) = self.cells.get(&(x + dx, y + dy)) {
+                    bucket.iter().for_each(|&i| f(i));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use glam::{vec2, Mat4, Vec2, Vec4};
+use miniquad::*;
+
+use crate::shader;
+
+const GLYPH_ROWS: usize = 5;
+const GLYPH_COLS: usize = 3;
+const GLYPH_ADVANCE: f32 = 4.0;
+
+// 3x5 bitmap glyphs, one row per entry from top to bottom, leftmost pixel in the high bit
+fn glyph(c: char) -> [u8; GLYPH_ROWS] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        _ => [0; GLYPH_ROWS],
+    }
+}
+
+fn quad_buffers(ctx: &mut Context, capacity: usize) -> (Buffer, Buffer) {
+    let vertex_buffer = Buffer::stream(
+        ctx,
+        BufferType::VertexBuffer,
+        capacity * 4 * std::mem::size_of::<Vec2>(),
+    );
+    let indices = (0..capacity as u32)
+        .flat_map(|q| [4 * q, 4 * q + 1, 4 * q + 2, 4 * q, 4 * q + 2, 4 * q + 3])
+        .collect::<Vec<u32>>();
+    let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
+    (vertex_buffer, index_buffer)
+}
+
+pub struct TextRenderer {
+    pipeline: Pipeline,
+    bindings: Bindings,
+    capacity: usize,
+    vertices: Vec<Vec2>,
+}
+
+impl TextRenderer {
+    pub fn new(ctx: &mut Context) -> Result<TextRenderer, String> {
+        let capacity = 1024;
+        let (vertex_buffer, index_buffer) = quad_buffers(ctx, capacity);
+
+        let shader = shader::compile(
+            ctx,
+            "text",
+            shader::TEXT_VERTEX,
+            shader::TEXT_FRAGMENT,
+            shader::text_meta(),
+        )?;
+
+        let pipeline = Pipeline::new(
+            ctx,
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("pos", VertexFormat::Float2)],
+            shader,
+        );
+
+        Ok(TextRenderer {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![],
+            },
+            capacity,
+            vertices: vec![],
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    // `scale` is the size of one font pixel, a character is 3x5 pixels
+    pub fn push_str(&mut self, text: &str, pos: Vec2, scale: f32) {
+        for (col, c) in text.chars().enumerate() {
+            let origin = pos + vec2(col as f32 * GLYPH_ADVANCE * scale, 0.0);
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for x in 0..GLYPH_COLS {
+                    if bits & (0b100 >> x) == 0 {
+                        continue;
+                    }
+                    let corner = origin + vec2(x as f32, row as f32) * scale;
+                    self.vertices.extend([
+                        corner,
+                        corner + vec2(scale, 0.0),
+                        corner + vec2(scale, scale),
+                        corner + vec2(0.0, scale),
+                    ]);
+                }
+            }
+        }
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, mvp: Mat4, color: Vec4) {
+        let num_quads = self.vertices.len() / 4;
+        if num_quads == 0 {
+            return;
+        }
+        if num_quads > self.capacity {
+            self.bindings.vertex_buffers[0].delete();
+            self.bindings.index_buffer.delete();
+            self.capacity = num_quads.next_power_of_two();
+            let (vertex_buffer, index_buffer) = quad_buffers(ctx, self.capacity);
+            self.bindings.vertex_buffers[0] = vertex_buffer;
+            self.bindings.index_buffer = index_buffer;
+        }
+        self.bindings.vertex_buffers[0].update(ctx, &self.vertices);
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.apply_uniforms(&shader::TextUniforms { mvp, color });
+        ctx.draw(0, num_quads as i32 * 6, 1);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+struct Span {
+    name: &'static str,
+    // relative to the creation of the trace
+    start: Duration,
+    duration: Duration,
+}
+
+// timed spans of the simulation phases, in the order they ended
+pub struct Trace {
+    origin: Instant,
+    spans: Vec<Span>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace {
+            origin: Instant::now(),
+            spans: vec![],
+        }
+    }
+
+    // records a span from `start` until now
+    pub fn span(&mut self, name: &'static str, start: Instant) {
+        self.spans.push(Span {
+            name,
+            start: start.duration_since(self.origin),
+            duration: start.elapsed(),
+        });
+    }
+
+    // writes the spans as complete events of the Chrome tracing format, which chrome://tracing
+    // and Perfetto can open
+    pub fn write_chrome(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{{\"traceEvents\":[")?;
+        for (i, span) in self.spans.iter().enumerate() {
+            writeln!(
+                out,
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\"ts\":{:.3},\"dur\":{:.3}}}{}",
+                span.name,
+                span.start.as_secs_f64() * 1e6,
+                span.duration.as_secs_f64() * 1e6,
+                if i + 1 < self.spans.len() { "," } else { "" }
+            )?;
+        }
+        writeln!(out, "]}}")?;
+        out.flush()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::physics::{FlagParams, Physics};
+
+    #[test]
+    fn chrome_trace_is_json_with_a_span_per_phase() {
+        let mut physics = Physics::new(&[FlagParams::builder().grid(4, 4).build().unwrap()]);
+        physics.start_trace();
+        for _ in 0..3 {
+            physics.step(1.0 / 60.0);
+        }
+        let path = std::env::temp_dir().join(format!("flags-{}-trace.json", std::process::id()));
+        physics.take_trace().unwrap().write_chrome(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let trace: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let names = events
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        let phases = [
+            "pin_paths",
+            "flags",
+            "stitches",
+            "interaction",
+            "pin_check",
+            "step",
+        ];
+        assert_eq!(names, phases.repeat(3));
+        for e in events {
+            assert_eq!(e["ph"], "X");
+            assert!(e["ts"].as_f64().unwrap() >= 0.0 && e["dur"].as_f64().unwrap() >= 0.0);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use flags::cloth3d::{Cloth, Sphere};
+use glam::{vec3, vec4, Mat4, Vec3, Vec4};
+use miniquad::*;
+
+use crate::{shader, DT};
+
+// the flag of the 3D view, hanging from its left edge with the wind blowing along it
+const CORNER: Vec3 = vec3(0.0, 300.0, 0.0);
+const SIZE: f32 = 1000.0;
+const GRAVITY: Vec3 = vec3(0.0, -200.0, 0.0);
+const WIND: Vec3 = vec3(300.0, 0.0, 60.0);
+const SUB_STEPS: usize = 10;
+const SPHERE: Sphere = Sphere {
+    center: vec3(550.0, -200.0, 200.0),
+    radius: 150.0,
+};
+const SPHERE_SEGMENTS: usize = 48;
+// the fixed camera looks at the middle of the flag from the front right
+const EYE: Vec3 = vec3(1400.0, 300.0, 1500.0);
+const TARGET: Vec3 = vec3(500.0, 0.0, 0.0);
+const FOV: f32 = 0.8;
+const CLOTH_COLOR: Vec4 = vec4(0.5, 0.8, 1.0, 1.0);
+const SPHERE_COLOR: Vec4 = vec4(0.8, 0.5, 0.3, 1.0);
+
+pub struct Stage3d {
+    pipeline: Pipeline,
+    cloth_bindings: Bindings,
+    num_cloth_indices: i32,
+    sphere_bindings: Bindings,
+    num_sphere_indices: i32,
+    cloth: Cloth,
+    points: Vec<Vec3>,
+    paused: bool,
+}
+
+impl Stage3d {
+    pub fn new(ctx: &mut Context, width: usize, height: usize) -> Result<Stage3d, String> {
+        let mut cloth = Cloth::new(CORNER, SIZE, width, height)?;
+        cloth.set_gravity(GRAVITY);
+        cloth.set_wind(WIND);
+        cloth.add_sphere(SPHERE);
+
+        let shader = shader::compile(
+            ctx,
+            "3D",
+            shader::VERTEX_3D,
+            shader::FRAGMENT,
+            shader::meta_3d(),
+        )?;
+        let pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("pos", VertexFormat::Float3)],
+            shader,
+            PipelineParams {
+                primitive_type: PrimitiveType::Lines,
+                depth_test: Comparison::LessOrEqual,
+                depth_write: true,
+                ..Default::default()
+            },
+        );
+
+        let indices = cloth.get_indices();
+        let cloth_bindings = Bindings {
+            vertex_buffers: vec![Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                cloth.num_nodes() * std::mem::size_of::<Vec3>(),
+            )],
+            index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &indices),
+            images: vec![],
+        };
+
+        let (vertices, sphere_indices) = sphere_lines(SPHERE);
+        let sphere_bindings = Bindings {
+            vertex_buffers: vec![Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices)],
+            index_buffer: Buffer::immutable(ctx, BufferType::IndexBuffer, &sphere_indices),
+            images: vec![],
+        };
+
+        Ok(Stage3d {
+            pipeline,
+            cloth_bindings,
+            num_cloth_indices: indices.len() as i32,
+            sphere_bindings,
+            num_sphere_indices: sphere_indices.len() as i32,
+            cloth,
+            points: vec![],
+            paused: false,
+        })
+    }
+}
+
+// three great circles of `sphere`, as pairs of indices into the vertices
+fn sphere_lines(sphere: Sphere) -> (Vec<Vec3>, Vec<u32>) {
+    let step = std::f32::consts::TAU / SPHERE_SEGMENTS as f32;
+    let vertices = [Vec3::X, Vec3::Y, Vec3::Z]
+        .into_iter()
+        .flat_map(|axis| {
+            // two directions spanning the plane of the circle around `axis`
+            let u = axis.cross(Vec3::ONE).normalize();
+            let w = axis.cross(u);
+            (0..SPHERE_SEGMENTS).map(move |i| {
+                let (sin, cos) = (i as f32 * step).sin_cos();
+                sphere.center + (u * cos + w * sin) * sphere.radius
+            })
+        })
+        .collect();
+    let n = SPHERE_SEGMENTS as u32;
+    let indices = (0..3)
+        .flat_map(|c| (0..n).flat_map(move |i| [c * n + i, c * n + (i + 1) % n]))
+        .collect();
+    (vertices, indices)
+}
+
+impl EventHandler for Stage3d {
+    fn update(&mut self, _: &mut Context) {
+        if self.paused {
+            return;
+        }
+        for _ in 0..SUB_STEPS {
+            self.cloth.step(DT / SUB_STEPS as f32);
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context) {
+        self.cloth.write_points(&mut self.points);
+        self.cloth_bindings.vertex_buffers[0].update(ctx, &self.points);
+
+        let (width, height) = ctx.screen_size();
+        let proj = Mat4::perspective_rh_gl(FOV, width / height, 10.0, 10000.0)
+            * Mat4::look_at_rh(EYE, TARGET, Vec3::Y);
+
+        ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+        ctx.apply_pipeline(&self.pipeline);
+        for (bindings, num_indices, color) in [
+            (&self.cloth_bindings, self.num_cloth_indices, CLOTH_COLOR),
+            (&self.sphere_bindings, self.num_sphere_indices, SPHERE_COLOR),
+        ] {
+            ctx.apply_bindings(bindings);
+            ctx.apply_uniforms(&shader::Uniforms3d {
+                mvp: proj,
+                line_color: color,
+            });
+            ctx.draw(0, num_indices, 1);
+        }
+        ctx.end_render_pass();
+
+        ctx.commit_frame();
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _: KeyMods, _: bool) {
+        match keycode {
+            KeyCode::Space => self.paused = !self.paused,
+            KeyCode::W => {
+                let wind = if self.cloth.wind() == Vec3::ZERO {
+                    WIND
+                } else {
+                    Vec3::ZERO
+                };
+                self.cloth.set_wind(wind);
+            }
+            KeyCode::Escape => ctx.quit(),
+            _ => (),
+        }
+    }
+}